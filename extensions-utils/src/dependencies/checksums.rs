@@ -0,0 +1,152 @@
+//! Verification of downloaded files against expected SHA-256 hashes.
+use crate::dependencies::download::{download_file, DownloadOptions};
+
+use anyhow::{bail, Context};
+use fn_error_context::context;
+use sha2::{Digest, Sha256};
+use slog::Logger;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use url::Url;
+
+/// The SHA-256 hashes of a set of files, as listed in a `SHA256SUMS` file.
+///
+/// The IC CI publishes one such file per commit next to the canister wasms and next to the binaries of each platform.
+#[derive(Debug, Default)]
+pub struct Sha256Sums {
+    /// Lowercase hex encoded hashes, by file name.
+    sums: BTreeMap<String, String>,
+}
+
+impl Sha256Sums {
+    /// Parses the output of `sha256sum`, i.e. lines of the form `<hash>  <file name>` or `<hash> *<file name>`.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut sums = BTreeMap::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let Some((hash, file_name)) = line.split_once(char::is_whitespace) else {
+                bail!("Malformed line in SHA256SUMS: '{line}'");
+            };
+            if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                bail!("Malformed hash in SHA256SUMS: '{line}'");
+            }
+            let file_name = file_name.trim_start().trim_start_matches('*');
+            sums.insert(file_name.to_string(), hash.to_ascii_lowercase());
+        }
+        Ok(Self { sums })
+    }
+
+    /// Downloads and parses a `SHA256SUMS` file, retrying like any other download.
+    #[context("Failed to get the expected SHA-256 hashes from '{}'.", url)]
+    pub async fn download(
+        logger: &Logger,
+        url: &Url,
+        options: &DownloadOptions,
+    ) -> anyhow::Result<Self> {
+        // The list may change while the CI is still publishing, so it is not kept between runs.
+        let dir = tempfile::tempdir().with_context(|| "Failed to create a temporary directory")?;
+        let path = dir.path().join("SHA256SUMS");
+        download_file(logger, url, &path, options).await?;
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read '{}'.", path.display()))?;
+        Self::parse(&text)
+    }
+
+    /// The expected hash of a file.
+    ///
+    /// # Errors
+    /// - Returns an error if the file is not listed, as the file cannot be verified.
+    pub fn get(&self, file_name: &str) -> anyhow::Result<&str> {
+        self.sums
+            .get(file_name)
+            .map(String::as_str)
+            .with_context(|| format!("No SHA-256 hash is published for '{file_name}'."))
+    }
+}
+
+/// The lowercase hex encoded SHA-256 hash of some bytes.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Checks that the bytes of a file have the expected SHA-256 hash.
+///
+/// # Errors
+/// - Returns an error naming the file, the expected hash and the actual hash if they differ.
+pub fn verify_sha256(file_name: &str, bytes: &[u8], expected_sha256: &str) -> anyhow::Result<()> {
+    check_sha256(file_name, &sha256_hex(bytes), expected_sha256)
+}
+
+/// Checks that a file on disk has the expected SHA-256 hash, without reading it into memory.
+///
+/// # Errors
+/// - Returns an error if the file cannot be read.
+/// - Returns an error naming the file, the expected hash and the actual hash if they differ.
+pub fn verify_file_sha256(
+    file_name: &str,
+    path: &Path,
+    expected_sha256: &str,
+) -> anyhow::Result<()> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open '{}'.", path.display()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("Failed to read '{}'.", path.display()))?;
+    check_sha256(file_name, &hex::encode(hasher.finalize()), expected_sha256)
+}
+
+/// Compares an actual hash with the expected one.
+fn check_sha256(file_name: &str, actual_sha256: &str, expected_sha256: &str) -> anyhow::Result<()> {
+    if !actual_sha256.eq_ignore_ascii_case(expected_sha256) {
+        bail!(
+            "SHA-256 mismatch for '{file_name}': expected {expected_sha256} but got {actual_sha256}."
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The SHA-256 hash of the empty string.
+    const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn test_parse_sha256sums() {
+        let sums = Sha256Sums::parse(&format!(
+            "{EMPTY_SHA256}  registry-canister.wasm.gz\n{}  *ic-admin.gz\n",
+            EMPTY_SHA256.to_uppercase()
+        ))
+        .unwrap();
+        assert_eq!(sums.get("registry-canister.wasm.gz").unwrap(), EMPTY_SHA256);
+        assert_eq!(sums.get("ic-admin.gz").unwrap(), EMPTY_SHA256);
+        assert!(sums.get("ledger-canister.wasm.gz").is_err());
+        assert!(Sha256Sums::parse("not-a-hash  file").is_err());
+    }
+
+    #[test]
+    fn test_verify_sha256() {
+        assert!(verify_sha256("empty", b"", EMPTY_SHA256).is_ok());
+        assert_eq!(
+            verify_sha256("one", b"1", EMPTY_SHA256)
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "SHA-256 mismatch for 'one': expected {EMPTY_SHA256} but got {}.",
+                sha256_hex(b"1")
+            )
+        );
+    }
+
+    #[test]
+    fn test_verify_file_sha256() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("empty");
+        std::fs::write(&path, b"").unwrap();
+        assert!(verify_file_sha256("empty", &path, EMPTY_SHA256).is_ok());
+        std::fs::write(&path, b"1").unwrap();
+        assert!(verify_file_sha256("one", &path, EMPTY_SHA256).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! Downloads over HTTP that survive flaky connections.
+//!
+//! Bodies are streamed to disk.  Failed attempts are retried with exponential backoff, and each retry resumes where
+//! the previous attempt stopped, with an HTTP range request.
+use anyhow::{anyhow, bail, Context};
+use backoff::backoff::Backoff;
+use backoff::ExponentialBackoffBuilder;
+use fn_error_context::context;
+use reqwest::header::RANGE;
+use reqwest::StatusCode;
+use slog::{warn, Logger};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use url::Url;
+
+/// The environment variable that sets how many times a failed download is retried.
+pub const DOWNLOAD_RETRIES_ENV: &str = "DFX_DOWNLOAD_RETRIES";
+
+/// The environment variable that sets, in seconds, how long a download waits for the server.
+pub const DOWNLOAD_TIMEOUT_ENV: &str = "DFX_DOWNLOAD_TIMEOUT";
+
+/// The environment variable that, set to `1`, allows downloads that have no pinned SHA-256 hash.
+pub const ALLOW_UNVERIFIED_DOWNLOADS_ENV: &str = "DFX_ALLOW_UNVERIFIED_DOWNLOADS";
+
+/// How persistent a download is, and what it may download.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DownloadOptions {
+    /// How many times a failed attempt is retried.
+    pub retries: u32,
+    /// How long to wait for the response, and then for each chunk of the body, before an attempt fails.
+    pub timeout: Duration,
+    /// Whether files without a known SHA-256 hash may be downloaded.  They are not verified.
+    pub allow_unverified: bool,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            retries: 5,
+            timeout: Duration::from_secs(60),
+            allow_unverified: false,
+        }
+    }
+}
+
+impl DownloadOptions {
+    /// The default options, changed by `DFX_DOWNLOAD_RETRIES`, `DFX_DOWNLOAD_TIMEOUT` and
+    /// `DFX_ALLOW_UNVERIFIED_DOWNLOADS` if they are set.
+    ///
+    /// # Errors
+    /// - Returns an error if a variable is set to something other than a number, or `0` or `1` respectively.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut options = Self::default();
+        if let Ok(retries) = std::env::var(DOWNLOAD_RETRIES_ENV) {
+            options.retries = retries.parse().with_context(|| {
+                format!("{DOWNLOAD_RETRIES_ENV} must be a number of retries, not '{retries}'.")
+            })?;
+        }
+        if let Ok(timeout) = std::env::var(DOWNLOAD_TIMEOUT_ENV) {
+            let seconds = timeout.parse().with_context(|| {
+                format!("{DOWNLOAD_TIMEOUT_ENV} must be a number of seconds, not '{timeout}'.")
+            })?;
+            options.timeout = Duration::from_secs(seconds);
+        }
+        if let Ok(allow_unverified) = std::env::var(ALLOW_UNVERIFIED_DOWNLOADS_ENV) {
+            options.allow_unverified = match allow_unverified.as_str() {
+                "1" => true,
+                "0" => false,
+                _ => bail!(
+                    "{ALLOW_UNVERIFIED_DOWNLOADS_ENV} must be 0 or 1, not '{allow_unverified}'."
+                ),
+            };
+        }
+        Ok(options)
+    }
+}
+
+/// The file a download of `target` is written to until it is complete: `target` with `.part` appended.
+pub fn partial_download_path(target: &Path) -> PathBuf {
+    let mut file_name = target.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".part");
+    target.with_file_name(file_name)
+}
+
+/// Downloads a file into `path`, continuing any download that an earlier attempt or an earlier run left there.
+///
+/// Connection failures, server errors and stalled transfers are retried; other HTTP errors are not.  If the download
+/// fails, `path` keeps what was received, so that the next download of the same file resumes from there.
+#[context("Failed to download '{}'.", source)]
+pub async fn download_file(
+    logger: &Logger,
+    source: &Url,
+    path: &Path,
+    options: &DownloadOptions,
+) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory '{}'.", parent.display()))?;
+    }
+    let client = reqwest::Client::new();
+    let mut retry_policy = ExponentialBackoffBuilder::new()
+        .with_initial_interval(Duration::from_secs(1))
+        .with_max_interval(Duration::from_secs(16))
+        .with_multiplier(2.0)
+        .with_max_elapsed_time(None)
+        .build();
+    let mut retries = 0;
+    loop {
+        match download_attempt(&client, source, path, options.timeout).await {
+            Ok(()) => return Ok(()),
+            Err(backoff::Error::Transient { err, .. }) if retries < options.retries => {
+                retries += 1;
+                let delay = retry_policy
+                    .next_backoff()
+                    .unwrap_or(retry_policy.max_interval);
+                warn!(
+                    logger,
+                    "{err:#} Retrying in {}s ({retries}/{}).",
+                    delay.as_secs(),
+                    options.retries;
+                    "url" => %source,
+                    "retry" => retries
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(backoff::Error::Transient { err, .. } | backoff::Error::Permanent(err)) => {
+                return Err(err)
+            }
+        }
+    }
+}
+
+/// Makes one attempt to download the rest of a file, appending it to what `path` already holds.
+///
+/// Errors are transient if another attempt may succeed.
+async fn download_attempt(
+    client: &reqwest::Client,
+    source: &Url,
+    path: &Path,
+    timeout: Duration,
+) -> Result<(), backoff::Error<anyhow::Error>> {
+    let offset = fs::metadata(path)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+    let mut request = client.get(source.clone());
+    if offset > 0 {
+        request = request.header(RANGE, format!("bytes={offset}-"));
+    }
+    let mut response = tokio::time::timeout(timeout, request.send())
+        .await
+        .map_err(|_| {
+            backoff::Error::transient(anyhow!("No response within {}s.", timeout.as_secs()))
+        })?
+        .map_err(|err| backoff::Error::transient(anyhow!(err).context("Failed to connect.")))?;
+    let status = response.status();
+    let resume = match status {
+        StatusCode::PARTIAL_CONTENT => true,
+        StatusCode::RANGE_NOT_SATISFIABLE => {
+            // What was downloaded before does not fit the file on the server, so start over.
+            fs::remove_file(path)
+                .with_context(|| format!("Failed to remove '{}'.", path.display()))
+                .map_err(backoff::Error::permanent)?;
+            return Err(backoff::Error::transient(anyhow!(
+                "Could not resume the download."
+            )));
+        }
+        // The server ignored the range and sent the whole file.
+        status if status.is_success() => false,
+        status if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS => {
+            return Err(backoff::Error::transient(anyhow!(
+                "The server returned {status}."
+            )))
+        }
+        status => {
+            return Err(backoff::Error::permanent(anyhow!(
+                "The server returned {status}."
+            )))
+        }
+    };
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resume)
+        .truncate(!resume)
+        .open(path)
+        .with_context(|| format!("Failed to open '{}'.", path.display()))
+        .map_err(backoff::Error::permanent)?;
+    loop {
+        let chunk = tokio::time::timeout(timeout, response.chunk())
+            .await
+            .map_err(|_| {
+                backoff::Error::transient(anyhow!(
+                    "The download stalled for {}s.",
+                    timeout.as_secs()
+                ))
+            })?
+            .map_err(|err| {
+                backoff::Error::transient(anyhow!(err).context("The download was interrupted."))
+            })?;
+        let Some(chunk) = chunk else {
+            return Ok(());
+        };
+        file.write_all(&chunk)
+            .with_context(|| format!("Failed to write '{}'.", path.display()))
+            .map_err(backoff::Error::permanent)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_download_path() {
+        assert_eq!(
+            partial_download_path(Path::new("wasms/ledger-canister.wasm")),
+            Path::new("wasms/ledger-canister.wasm.part")
+        );
+    }
+}
@@ -1,10 +1,12 @@
-use backoff::future::retry;
-use backoff::ExponentialBackoffBuilder;
+use crate::dependencies::checksums::Sha256Sums;
+use crate::dependencies::download::DownloadOptions;
+use crate::dependencies::download_wasms::download_gz;
+use crate::new_logger;
 use flate2::read::GzDecoder;
 use std::path::Path;
-use std::time::Duration;
 use std::{fs, io::copy};
 use tokio::runtime::Runtime;
+use url::Url;
 
 pub fn download_ic_binary(replica_rev: &str, binary_name: &str, destination_path: &Path) {
     let arch = match std::env::consts::ARCH {
@@ -19,17 +21,39 @@ pub fn download_ic_binary(replica_rev: &str, binary_name: &str, destination_path
         _ => panic!("Unsupported OS"),
     };
 
-    let url = format!(
-        "https://download.dfinity.systems/ic/{replica_rev}/binaries/{arch}-{os}/{binary_name}.gz",
-        arch = arch,
-        os = os,
-        binary_name = binary_name,
-    );
-    println!("Downloading {}", url);
+    let binaries_url =
+        format!("https://download.dfinity.systems/ic/{replica_rev}/binaries/{arch}-{os}");
+    let url = format!("{binaries_url}/{binary_name}.gz");
 
-    let bytes = Runtime::new().unwrap().block_on(download_bytes(&url));
-    let mut d = GzDecoder::new(&*bytes);
+    let runtime = Runtime::new().unwrap();
+    let logger = new_logger();
+    let options = DownloadOptions::from_env().unwrap_or_else(|err| panic!("{err:#}"));
+    let sha256_sums_url =
+        Url::parse(&format!("{binaries_url}/SHA256SUMS")).expect("Invalid download URL");
+    let sha256_sums = runtime
+        .block_on(Sha256Sums::download(&logger, &sha256_sums_url, &options))
+        .unwrap_or_else(|err| panic!("{err:#}"));
+    let gz_name = format!("{binary_name}.gz");
+    let expected_sha256 = sha256_sums
+        .get(&gz_name)
+        .unwrap_or_else(|err| panic!("{err}"));
+    // The `.gz` file is kept in a cache per IC commit and platform, so that an interrupted download is resumed by the
+    // next build and a complete one is not downloaded again.
+    let gz_path = std::env::temp_dir()
+        .join("dfx-extensions-ic-binaries")
+        .join(replica_rev)
+        .join(format!("{arch}-{os}"))
+        .join(&gz_name);
+    runtime
+        .block_on(download_gz(
+            &logger,
+            &Url::parse(&url).expect("Invalid download URL"),
+            &gz_path,
+            Some(expected_sha256),
+        ))
+        .unwrap_or_else(|err| panic!("{err:#}"));
     let tempdir = tempfile::tempdir().expect("Failed to create temp dir");
+    let mut d = GzDecoder::new(fs::File::open(&gz_path).expect("Failed to open the download"));
     let temp_file = tempdir.path().join(binary_name);
     let mut temp = fs::File::create(&temp_file).expect("Failed to create the file");
     copy(&mut d, &mut temp).expect("Failed to copy content");
@@ -56,23 +80,3 @@ pub fn download_ic_binary(replica_rev: &str, binary_name: &str, destination_path
         );
     });
 }
-
-async fn download_bytes(url: &str) -> Vec<u8> {
-    let retry_policy = ExponentialBackoffBuilder::new()
-        .with_initial_interval(Duration::from_secs(1))
-        .with_max_interval(Duration::from_secs(16))
-        .with_multiplier(2.0)
-        .with_max_elapsed_time(Some(Duration::from_secs(300)))
-        .build();
-    let resp = retry(retry_policy, || async {
-        match reqwest::get(url).await {
-            Ok(response) => Ok(response),
-            Err(err) => Err(backoff::Error::transient(err)),
-        }
-    })
-    .await
-    .unwrap();
-
-    let bytes = resp.bytes().await.expect("Failed to read response");
-    bytes.to_vec()
-}
@@ -1,94 +1,208 @@
 pub mod nns;
 pub mod sns;
 
-use anyhow::Context;
+use crate::dependencies::checksums::{verify_file_sha256, Sha256Sums};
+use crate::dependencies::download::{
+    download_file, partial_download_path, DownloadOptions, ALLOW_UNVERIFIED_DOWNLOADS_ENV,
+};
+use crate::Step;
+
+use anyhow::{bail, Context};
 use dfx_core::fs;
 use flate2::read::GzDecoder;
 use fn_error_context::context;
+use slog::{debug, warn, Logger};
 
 use url::Url;
 
 use std::path::{Component, Path};
 
 /// Downloads a file (this function should be used for canister modules)
+///
+/// The file is stored as downloaded, so `expected_sha256` is the hash of the `.gz` file.
 #[context("Failed to download '{:?}' from '{:?}'.", target, source.as_str())]
-pub async fn download_gz(source: &Url, target: &Path) -> anyhow::Result<()> {
-    download_gz_and_maybe_ungzip(source, target, false).await
+pub async fn download_gz(
+    logger: &Logger,
+    source: &Url,
+    target: &Path,
+    expected_sha256: Option<&str>,
+) -> anyhow::Result<()> {
+    download_gz_and_maybe_ungzip(logger, source, target, false, expected_sha256).await
 }
 
 /// Downloads and unzips a file (this function should be used for x86 binaries)
+///
+/// `expected_sha256` is the hash of the `.gz` file, before it is unzipped.
 #[context("Failed to download and unzip '{:?}' from '{:?}'.", target, source.as_str())]
-pub async fn download_gz_and_ungzip(source: &Url, target: &Path) -> anyhow::Result<()> {
-    download_gz_and_maybe_ungzip(source, target, true).await
+pub async fn download_gz_and_ungzip(
+    logger: &Logger,
+    source: &Url,
+    target: &Path,
+    expected_sha256: Option<&str>,
+) -> anyhow::Result<()> {
+    download_gz_and_maybe_ungzip(logger, source, target, true, expected_sha256).await
 }
 
+/// Downloads a `.gz` file, checks its SHA-256 hash and stores it, unzipped if `unzip` is set.
+///
+/// A file that has already been downloaded is kept if it still has the expected hash and downloaded again otherwise.
+/// Unzipped files cannot be checked once they have been stored.
+///
+/// # Errors
+/// - Returns an error if the downloaded file does not have the expected hash.
+/// - Returns an error if no hash is expected, unless `DFX_ALLOW_UNVERIFIED_DOWNLOADS` is set to `1`.
 pub async fn download_gz_and_maybe_ungzip(
+    logger: &Logger,
     source: &Url,
     target: &Path,
     unzip: bool,
+    expected_sha256: Option<&str>,
 ) -> anyhow::Result<()> {
+    let file_name = target.to_string_lossy();
+    let options = DownloadOptions::from_env()?;
+    if expected_sha256.is_none() {
+        if !options.allow_unverified {
+            bail!(
+                "No SHA-256 hash is pinned for {file_name}, so it cannot be verified.  Set {ALLOW_UNVERIFIED_DOWNLOADS_ENV}=1 to use it anyway."
+            );
+        }
+        warn!(
+            logger,
+            "No SHA-256 hash is pinned for {file_name}; it is not verified.";
+            "file" => %file_name
+        );
+    }
     if target.exists() {
-        println!("Already downloaded: {}", target.to_string_lossy());
-        return Ok(());
+        match expected_sha256 {
+            Some(expected_sha256) if !unzip => {
+                if verify_file_sha256(&file_name, target, expected_sha256).is_ok() {
+                    debug!(logger, "Already downloaded: {file_name}"; "file" => %file_name);
+                    return Ok(());
+                }
+                warn!(
+                    logger,
+                    "Cached file has an unexpected SHA-256 hash: {file_name}";
+                    "file" => %file_name
+                );
+            }
+            _ => {
+                debug!(logger, "Already downloaded: {file_name}"; "file" => %file_name);
+                return Ok(());
+            }
+        }
+    }
+    let step = Step::start(logger, format!("Downloading {file_name}"));
+    debug!(logger, "Downloading from {source}"; "file" => %file_name, "url" => %source);
+    // The `.gz` file is kept next to the target until it is verified, so that an interrupted download can be resumed.
+    let downloaded = partial_download_path(target);
+    download_file(logger, source, &downloaded, &options).await?;
+    if let Some(expected_sha256) = expected_sha256 {
+        if let Err(err) = verify_file_sha256(&file_name, &downloaded, expected_sha256) {
+            // Do not resume from a corrupt file next time.
+            std::fs::remove_file(&downloaded)
+                .with_context(|| format!("Failed to remove '{}'.", downloaded.display()))?;
+            return Err(err);
+        }
     }
-    println!(
-        "Downloading {}\n  from .gz: {}",
-        target.to_string_lossy(),
-        source.as_str()
-    );
-    let response = reqwest::get(source.clone())
-        .await
-        .with_context(|| "Failed to connect")?
-        .bytes()
-        .await
-        .with_context(|| "Download was interrupted")?;
 
-    let target_parent = target
-        .parent()
-        .unwrap_or_else(|| Path::new(Component::CurDir.as_os_str()));
-    let tmp_dir = tempfile::TempDir::new_in(target_parent)
-        .with_context(|| "Failed to create temporary directory for download")?;
-    let downloaded_filename = {
-        let filename = tmp_dir.path().join("wasm");
-        let mut file = std::fs::File::create(&filename).with_context(|| {
+    if unzip {
+        let target_parent = target
+            .parent()
+            .unwrap_or_else(|| Path::new(Component::CurDir.as_os_str()));
+        let tmp_dir = tempfile::TempDir::new_in(target_parent)
+            .with_context(|| "Failed to create temporary directory for download")?;
+        let unzipped_filename = tmp_dir.path().join("wasm");
+        let mut file = std::fs::File::create(&unzipped_filename).with_context(|| {
             format!(
                 "Failed to write temp file when downloading '{}'.",
-                filename.display()
+                unzipped_filename.display()
             )
         })?;
-        if unzip {
-            let mut decoder = GzDecoder::new(&response[..]);
-            std::io::copy(&mut decoder, &mut file)
-                .with_context(|| format!("Failed to unzip WASM to '{}'", filename.display()))?;
-        } else {
-            std::io::copy(&mut response.as_ref(), &mut file)
-                .with_context(|| format!("Failed copy WASM to '{}'", filename.display()))?;
-        }
-        filename
-    };
-    fs::rename(&downloaded_filename, target).with_context(|| {
-        format!(
-            "Failed to move downloaded tempfile '{}' to '{}'.",
-            downloaded_filename.display(),
-            target.display()
-        )
-    })?;
+        let gz = std::fs::File::open(&downloaded)
+            .with_context(|| format!("Failed to open '{}'.", downloaded.display()))?;
+        let mut decoder = GzDecoder::new(gz);
+        std::io::copy(&mut decoder, &mut file).with_context(|| {
+            format!("Failed to unzip WASM to '{}'", unzipped_filename.display())
+        })?;
+        fs::rename(&unzipped_filename, target).with_context(|| {
+            format!(
+                "Failed to move downloaded tempfile '{}' to '{}'.",
+                unzipped_filename.display(),
+                target.display()
+            )
+        })?;
+        std::fs::remove_file(&downloaded)
+            .with_context(|| format!("Failed to remove '{}'.", downloaded.display()))?;
+    } else {
+        fs::rename(&downloaded, target).with_context(|| {
+            format!(
+                "Failed to move downloaded file '{}' to '{}'.",
+                downloaded.display(),
+                target.display()
+            )
+        })?;
+    }
+    step.finish();
     Ok(())
 }
 
 /// Downloads wasm file from the main IC repo CI.
+///
+/// The file is verified against the hashes that the IC CI publishes for the commit.
 #[context("Failed to download {} from the IC CI.", wasm_name)]
 pub async fn download_ic_repo_wasm(
+    logger: &Logger,
     wasm_name: &str,
     ic_commit: &str,
     wasm_dir: &Path,
+    sha256_sums: &Sha256Sums,
 ) -> anyhow::Result<()> {
     fs::create_dir_all(wasm_dir)
         .with_context(|| format!("Failed to create wasm directory: '{}'", wasm_dir.display()))?;
     let final_path = wasm_dir.join(wasm_name);
+    let url = ic_repo_wasm_url(wasm_name, ic_commit)?;
+    let expected_sha256 = sha256_sums.get(&format!("{wasm_name}.gz"))?;
+    download_gz(logger, &url, &final_path, Some(expected_sha256)).await
+}
+
+/// The URL from which the IC CI serves a wasm file.
+pub fn ic_repo_wasm_url(wasm_name: &str, ic_commit: &str) -> anyhow::Result<Url> {
     let url_str =
         format!("https://download.dfinity.systems/ic/{ic_commit}/canisters/{wasm_name}.gz");
-    let url = Url::parse(&url_str)
-      .with_context(|| format!("Could not determine download URL. Are ic_commit '{ic_commit}' and wasm_name '{wasm_name}' valid?"))?;
-    download_gz(&url, &final_path).await
+    Url::parse(&url_str)
+      .with_context(|| format!("Could not determine download URL. Are ic_commit '{ic_commit}' and wasm_name '{wasm_name}' valid?"))
+}
+
+/// Checks that a directory contains all the given wasm files.
+///
+/// # Errors
+/// - Returns an error listing every missing file.
+pub fn verify_wasms_are_present(wasm_dir: &Path, wasm_names: &[&str]) -> anyhow::Result<()> {
+    let missing: Vec<String> = wasm_names
+        .iter()
+        .map(|wasm_name| wasm_dir.join(wasm_name))
+        .filter(|path| !path.exists())
+        .map(|path| format!("  {}", path.display()))
+        .collect();
+    if !missing.is_empty() {
+        bail!(
+            "Missing {} wasm file(s) in '{}':\n{}",
+            missing.len(),
+            wasm_dir.display(),
+            missing.join("\n")
+        );
+    }
+    Ok(())
+}
+
+/// Gets the SHA-256 hashes of the wasm files that the IC CI built for a commit.
+#[context("Failed to get the wasm hashes for IC commit '{}'.", ic_commit)]
+pub async fn ic_repo_wasm_sha256_sums(
+    logger: &Logger,
+    ic_commit: &str,
+) -> anyhow::Result<Sha256Sums> {
+    let url = Url::parse(&format!(
+        "https://download.dfinity.systems/ic/{ic_commit}/canisters/SHA256SUMS"
+    ))?;
+    Sha256Sums::download(logger, &url, &DownloadOptions::from_env()?).await
 }
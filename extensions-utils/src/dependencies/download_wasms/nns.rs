@@ -3,37 +3,59 @@
 use std::path::{Path, PathBuf};
 
 use fn_error_context::context;
+use slog::Logger;
 
 use crate::{dependencies::dfx::NNS_SNS_REPLICA_REV, download_ic_repo_wasm};
 
-use super::sns::download_sns_wasms;
+use super::ic_repo_wasm_sha256_sums;
 
-/// Downloads all the core NNS wasms, excluding only the front-end wasms II and NNS-dapp.
+/// Downloads all the core NNS wasms from the given IC commit, excluding the SNS wasms and the front-end wasms II and
+/// NNS-dapp.
+///
+/// The wasms are verified against the hashes published by the IC CI.
 #[context("Failed to download NNS wasm files.")]
-pub async fn download_nns_wasms(dfx_cache_path: &Path) -> anyhow::Result<()> {
-    let ic_commit = std::env::var("DFX_IC_COMMIT").unwrap_or(NNS_SNS_REPLICA_REV.to_string());
+pub async fn download_nns_wasms(
+    logger: &Logger,
+    ic_commit: &str,
+    dfx_cache_path: &Path,
+) -> anyhow::Result<()> {
     let wasm_dir = &nns_wasm_dir(dfx_cache_path);
-    for IcNnsInitCanister {
-        wasm_name,
-        test_wasm_name,
-        ..
-    } in [NNS_CORE.to_vec(), NNS_CORE_MANUAL.to_vec()].concat()
-    {
-        download_ic_repo_wasm(wasm_name, &ic_commit, wasm_dir).await?;
-        if let Some(test_wasm_name) = test_wasm_name {
-            download_ic_repo_wasm(test_wasm_name, &ic_commit, wasm_dir).await?;
-        }
+    let sha256_sums = ic_repo_wasm_sha256_sums(logger, ic_commit).await?;
+    for wasm_name in nns_ic_repo_wasm_names() {
+        download_ic_repo_wasm(logger, wasm_name, ic_commit, wasm_dir, &sha256_sums).await?;
     }
-    download_sns_wasms(&ic_commit, wasm_dir).await?;
     Ok(())
 }
 
+/// The basenames of the NNS wasm files taken from the IC repository, excluding the SNS wasms.
+///
+/// These are the files that `download_nns_wasms` downloads.
+pub fn nns_ic_repo_wasm_names() -> Vec<&'static str> {
+    NNS_CORE
+        .iter()
+        .chain(NNS_CORE_MANUAL.iter())
+        .flat_map(
+            |IcNnsInitCanister {
+                 wasm_name,
+                 test_wasm_name,
+                 ..
+             }| [Some(*wasm_name), *test_wasm_name],
+        )
+        .flatten()
+        .collect()
+}
+
+/// The IC commit that NNS wasms are taken from.  Set DFX_IC_COMMIT to use a different commit.
+pub fn nns_ic_commit() -> String {
+    std::env::var("DFX_IC_COMMIT").unwrap_or(NNS_SNS_REPLICA_REV.to_string())
+}
+
 /// The local directory where NNS wasm files are cached.  The directory is typically created on demand.
 pub fn nns_wasm_dir(dfx_cache_path: &Path) -> PathBuf {
     dfx_cache_path.join("wasms")
 }
 
-/// Configuration for an NNS canister installation as performed by `ic-nns-init`.
+/// Configuration for an NNS canister installation as performed by `dfx nns install`.
 ///
 /// Note: Other deployment methods may well use different settings.
 pub struct IcNnsInitCanister {
@@ -41,7 +63,7 @@ pub struct IcNnsInitCanister {
     pub canister_name: &'static str,
     /// The basename of the wasm file.
     pub wasm_name: &'static str,
-    /// ic-nns-init uses test wasms for some canisters but still requires the standard wasm to be present.  For unknown reasons.
+    /// The test wasm, if any, is installed instead of the standard wasm.
     pub test_wasm_name: Option<&'static str>,
     /// The id of the canister when installed by `dfx nns install`.
     pub canister_id: &'static str,
@@ -153,6 +175,34 @@ pub const ICP_INDEX: IcNnsInitCanister = IcNnsInitCanister {
     test_wasm_name: None,
     canister_id: "q3fc5-haaaa-aaaaa-aaahq-cai",
 };
+/// The mainnet ID of the ckETH ledger, on the fiduciary subnet.
+///
+/// `dfx nns install` installs its default test token here, instead of at `ICRC1_LEDGER`, if the local network has a
+/// fiduciary subnet.
+pub const CKETH_LEDGER_MAINNET_ID: &str = "ss2fx-dyaaa-aaaar-qacoq-cai";
+/// The mainnet ID of the ckETH index, on the fiduciary subnet.  See `CKETH_LEDGER_MAINNET_ID`.
+pub const CKETH_INDEX_MAINNET_ID: &str = "s3zol-vqaaa-aaaar-qacpa-cai";
+/// ckBTC ledger, installed at its mainnet ID by `dfx nns install --with-ckbtc`.
+pub const CKBTC_LEDGER: IcNnsInitCanister = IcNnsInitCanister {
+    canister_name: "ckbtc_ledger",
+    wasm_name: "ic-icrc1-ledger.wasm",
+    test_wasm_name: None,
+    canister_id: "mxzaz-hqaaa-aaaar-qaada-cai",
+};
+/// ckBTC index, installed at its mainnet ID by `dfx nns install --with-ckbtc`.
+pub const CKBTC_INDEX: IcNnsInitCanister = IcNnsInitCanister {
+    canister_name: "ckbtc_index",
+    wasm_name: "ic-icrc1-index-ng.wasm",
+    test_wasm_name: None,
+    canister_id: "n5wcd-faaaa-aaaar-qaaea-cai",
+};
+/// ckBTC minter, installed at its mainnet ID by `dfx nns install --with-ckbtc`.
+pub const CKBTC_MINTER: IcNnsInitCanister = IcNnsInitCanister {
+    canister_name: "ckbtc_minter",
+    wasm_name: "ic-ckbtc-minter.wasm",
+    test_wasm_name: None,
+    canister_id: "mqygn-kiaaa-aaaar-qaadq-cai",
+};
 /// Minimum data needed to download and deploy a standard canister via dfx deploy NAME.
 pub struct StandardCanister {
     /// The typical name of the canister, as seen in dfx.json or used in `dfx canister id NAME`.
@@ -163,13 +213,17 @@ pub struct StandardCanister {
     pub wasm_name: &'static str,
     /// The URL from which to download the wasm file.
     pub wasm_url: &'static str,
+    /// The SHA-256 hash of the file at `wasm_url`.  Without a pinned hash, the file is only downloaded if
+    /// `DFX_ALLOW_UNVERIFIED_DOWNLOADS` is set.
+    pub wasm_sha256: Option<&'static str>,
 }
 /// A canister that provides login as a service for other dapps.
 pub const INTERNET_IDENTITY: StandardCanister = StandardCanister {
     canister_name: "internet_identity",
     canister_id: "qhbym-qaaaa-aaaaa-aaafq-cai",
     wasm_name: "internet_identity_dev.wasm",
-    wasm_url: "https://github.com/dfinity/internet-identity/releases/download/release-2024-05-13/internet_identity_dev.wasm.gz"
+    wasm_url: "https://github.com/dfinity/internet-identity/releases/download/release-2024-05-13/internet_identity_dev.wasm.gz",
+    wasm_sha256: None,
 };
 /// Frontend dapp for voting and managing neurons.
 pub const NNS_DAPP: StandardCanister = StandardCanister {
@@ -177,6 +231,7 @@ pub const NNS_DAPP: StandardCanister = StandardCanister {
     canister_id: "qsgjb-riaaa-aaaaa-aaaga-cai",
     wasm_name: "nns-dapp_test.wasm",
     wasm_url: "https://github.com/dfinity/nns-dapp/releases/download/proposal-129748/nns-dapp_test.wasm.gz",
+    wasm_sha256: None,
 };
 /// SNS aggregator canister.
 pub const SNS_AGGREGATOR: StandardCanister = StandardCanister {
@@ -184,6 +239,7 @@ pub const SNS_AGGREGATOR: StandardCanister = StandardCanister {
     canister_id: "sgymv-uiaaa-aaaaa-aaaia-cai",
     wasm_name: "sns_aggregator.wasm.gz",
     wasm_url: "https://github.com/dfinity/nns-dapp/releases/download/proposal-129748/sns_aggregator.wasm.gz",
+    wasm_sha256: None,
 };
 /// Canister that can be used instead of cycle wallets
 pub const CYCLES_LEDGER: StandardCanister = StandardCanister {
@@ -191,8 +247,9 @@ pub const CYCLES_LEDGER: StandardCanister = StandardCanister {
     canister_id: "um5iw-rqaaa-aaaaq-qaaba-cai",
     wasm_name: "cycles_ledger.wasm.gz",
     wasm_url: "https://github.com/dfinity/cycles-ledger/releases/download/cycles-ledger-v0.2.8/cycles-ledger.wasm.gz",
+    wasm_sha256: None,
 };
-/// Backend canisters deployed by `ic-nns-init`.
+/// Backend canisters that are created and initialized together.
 pub const NNS_CORE: &[&IcNnsInitCanister; 12] = &[
     &NNS_REGISTRY,           // 0
     &NNS_GOVERNANCE,         // 1
@@ -212,7 +269,9 @@ pub const NNS_CORE_MANUAL: &[&IcNnsInitCanister; 3] = &[
     &ICRC1_INDEX,  // 14 - only in local deployment
     &ICP_INDEX,    // 15 - only in local deployment
 ];
-/// Frontend canisters are not deployed by `ic-nns-init`. The deployment is normal, like any other canister.
+/// The ckBTC canisters, in the order they are installed.
+pub const CKBTC: &[&IcNnsInitCanister; 3] = &[&CKBTC_LEDGER, &CKBTC_INDEX, &CKBTC_MINTER];
+/// Frontend canisters are not initialized with the core canisters. The deployment is normal, like any other canister.
 pub const NNS_FRONTEND: [&StandardCanister; 3] = [&INTERNET_IDENTITY, &NNS_DAPP, &SNS_AGGREGATOR];
 
 /// Test account with well known public & private keys, used in NNS_LEDGER, NNS_DAPP and third party projects.
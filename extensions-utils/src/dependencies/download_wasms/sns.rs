@@ -3,23 +3,55 @@ use std::path::Path;
 use anyhow;
 use fn_error_context::context;
 use futures_util::future::try_join_all;
+use slog::Logger;
 
 use crate::download_ic_repo_wasm;
 
+use super::{ic_repo_wasm_sha256_sums, verify_wasms_are_present};
+
 /// Downloads all the core SNS wasms.
+///
+/// The wasms are verified against the hashes published by the IC CI.
 #[context("Failed to download SNS wasm files.")]
-pub async fn download_sns_wasms(ic_commit: &str, wasms_dir: &Path) -> anyhow::Result<()> {
+pub async fn download_sns_wasms(
+    logger: &Logger,
+    ic_commit: &str,
+    wasms_dir: &Path,
+) -> anyhow::Result<()> {
+    let sha256_sums = ic_repo_wasm_sha256_sums(logger, ic_commit).await?;
     try_join_all(
         SNS_CANISTERS
             .iter()
             .map(|SnsCanisterInstallation { wasm_name, .. }| {
-                download_ic_repo_wasm(wasm_name, ic_commit, wasms_dir)
+                download_ic_repo_wasm(logger, wasm_name, ic_commit, wasms_dir, &sha256_sums)
             }),
     )
     .await?;
     Ok(())
 }
 
+/// Copies the core SNS wasms from a directory prepared in advance.  The network is not used.
+///
+/// # Errors
+/// - Returns an error listing every missing file if any of the wasms is not in `source_dir`.
+#[context("Failed to copy SNS wasm files from '{}'.", source_dir.display())]
+pub fn copy_sns_wasms(source_dir: &Path, wasms_dir: &Path) -> anyhow::Result<()> {
+    let wasm_names: Vec<&str> = SNS_CANISTERS
+        .iter()
+        .map(|canister| canister.wasm_name)
+        .collect();
+    verify_wasms_are_present(source_dir, &wasm_names)?;
+    dfx_core::fs::create_dir_all(wasms_dir)?;
+    for wasm_name in wasm_names {
+        let source = source_dir.join(wasm_name);
+        let target = wasms_dir.join(wasm_name);
+        if source != target {
+            dfx_core::fs::copy(&source, &target)?;
+        }
+    }
+    Ok(())
+}
+
 /// Information required for WASMs uploaded to the nns-sns-wasm canister.
 ///
 /// Note:  These wasms are not deployed by `ic nns install` but later by developers
@@ -7,7 +7,9 @@ use std::{
 };
 
 pub mod call;
+pub mod checksums;
 pub mod dfx;
+pub mod download;
 pub mod download_ic_binaries;
 pub mod download_wasms;
 
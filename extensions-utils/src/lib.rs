@@ -8,19 +8,22 @@ mod project;
 
 pub use dependencies::{
     call::call_extension_bundled_binary,
+    checksums::{sha256_hex, verify_file_sha256, verify_sha256, Sha256Sums},
     dfx::{call_dfx_bundled_binary, dfx_version},
+    download::{download_file, partial_download_path, DownloadOptions},
     download_ic_binaries::download_ic_binary,
     download_wasms::{
-        download_ic_repo_wasm,
+        download_gz, download_ic_repo_wasm, ic_repo_wasm_sha256_sums, ic_repo_wasm_url,
         nns::{
-            download_nns_wasms, nns_wasm_dir, IcNnsInitCanister, StandardCanister,
-            ED25519_TEST_ACCOUNT, NNS_CORE, NNS_CORE_MANUAL, NNS_FRONTEND, NNS_SNS_WASM,
-            SECP256K1_TEST_ACCOUNT,
+            download_nns_wasms, nns_ic_commit, nns_ic_repo_wasm_names, nns_wasm_dir,
+            IcNnsInitCanister, StandardCanister, ED25519_TEST_ACCOUNT, NNS_CORE, NNS_CORE_MANUAL,
+            NNS_FRONTEND, NNS_SNS_WASM, SECP256K1_TEST_ACCOUNT,
         },
-        sns::{download_sns_wasms, SnsCanisterInstallation, SNS_CANISTERS},
+        sns::{copy_sns_wasms, download_sns_wasms, SnsCanisterInstallation, SNS_CANISTERS},
+        verify_wasms_are_present,
     },
 };
-pub use logger::new_logger;
+pub use logger::{new_logger, LogFormat, LoggingOpts, Step};
 pub use project::import::import_canister_definitions;
 pub use project::network_mappings::get_network_mappings;
 
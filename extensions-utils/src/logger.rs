@@ -1,15 +1,29 @@
-use slog::{Drain, Logger};
+use clap::{ArgAction, Args, ValueEnum};
+use slog::{info, Drain, Key, Level, Logger};
+use std::fmt;
+use std::time::Instant;
 
 pub struct TermLogFormat<D>
 where
     D: slog_term::Decorator,
 {
     decorator: D,
+    /// Whether the key-values of a record are printed after the message.
+    show_kv: bool,
 }
 
 impl<D: slog_term::Decorator> TermLogFormat<D> {
     pub fn new(decorator: D) -> TermLogFormat<D> {
-        TermLogFormat { decorator }
+        TermLogFormat {
+            decorator,
+            show_kv: false,
+        }
+    }
+
+    /// Prints the key-values of every record and of its logger, such as canister names and durations, after the message.
+    pub fn with_key_values(mut self) -> TermLogFormat<D> {
+        self.show_kv = true;
+        self
     }
 }
 
@@ -33,6 +47,13 @@ impl<D: slog_term::Decorator> slog::Drain for TermLogFormat<D> {
             decorator.start_msg()?;
             write!(decorator, "{}", record.msg())?;
 
+            if self.show_kv {
+                let mut serializer = KeyValueWriter(&mut *decorator);
+                slog::KV::serialize(&record.kv(), record, &mut serializer)
+                    .and_then(|()| slog::KV::serialize(values, record, &mut serializer))
+                    .map_err(std::io::Error::other)?;
+            }
+
             decorator.start_whitespace()?;
             writeln!(decorator)?;
 
@@ -42,9 +63,126 @@ impl<D: slog_term::Decorator> slog::Drain for TermLogFormat<D> {
     }
 }
 
+/// Writes key-values as ` key=value`.
+struct KeyValueWriter<'a>(&'a mut dyn slog_term::RecordDecorator);
+
+impl slog::Serializer for KeyValueWriter<'_> {
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+        self.0.start_whitespace()?;
+        write!(self.0, " ")?;
+        self.0.start_key()?;
+        write!(self.0, "{key}")?;
+        self.0.start_separator()?;
+        write!(self.0, "=")?;
+        self.0.start_value()?;
+        write!(self.0, "{val}")?;
+        Ok(())
+    }
+}
+
+/// How log messages are printed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Plain messages for humans.
+    #[default]
+    Text,
+    /// One JSON object per line, with the level, the time and all key-values.
+    Json,
+}
+
+/// The flags that control the progress messages of an extension.
+///
+/// Messages go to stderr, so that the output of a command can be piped.
+#[derive(Args, Clone, Debug, Default)]
+pub struct LoggingOpts {
+    /// Print more progress messages, with details such as canister IDs. Repeat for even more.
+    #[arg(long, global = true, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Print fewer progress messages. Repeat to print only errors.
+    #[arg(long, global = true, action = ArgAction::Count)]
+    pub quiet: u8,
+
+    /// Print progress messages as `text` or as `json` lines.
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+}
+
+impl LoggingOpts {
+    /// The most detailed level that is printed: info by default, lowered by `--verbose` and raised by `--quiet`.
+    pub fn level(&self) -> Level {
+        let level = 4 + i64::from(self.verbose) - i64::from(self.quiet);
+        Level::from_usize(level.clamp(1, 6) as usize).unwrap_or(Level::Info)
+    }
+
+    /// Creates a logger that prints to stderr as chosen by the flags.
+    pub fn logger(&self) -> Logger {
+        let level = self.level();
+        match self.log_format {
+            LogFormat::Text => {
+                let decorator = slog_term::TermDecorator::new().stderr().build();
+                let format = TermLogFormat::new(decorator);
+                let format = if self.verbose > 0 {
+                    format.with_key_values()
+                } else {
+                    format
+                };
+                let drain = format.fuse().filter_level(level).ignore_res();
+                let drain = slog_async::Async::new(drain).build().fuse();
+                Logger::root(drain, slog::o!())
+            }
+            LogFormat::Json => {
+                let drain = slog_json::Json::new(std::io::stderr())
+                    .add_default_keys()
+                    .build()
+                    .fuse()
+                    .filter_level(level)
+                    .ignore_res();
+                let drain = slog_async::Async::new(drain).build().fuse();
+                Logger::root(drain, slog::o!())
+            }
+        }
+    }
+}
+
 pub fn new_logger() -> Logger {
-    let decorator = slog_term::TermDecorator::new().build();
-    let drain = TermLogFormat::new(decorator).fuse();
-    let drain = slog_async::Async::new(drain).build().fuse();
-    Logger::root(drain, slog::o!())
+    LoggingOpts::default().logger()
+}
+
+/// A step of a command, such as installing a canister.
+///
+/// The start of the step is logged when it is created and the end, with the duration, when it is finished.  Both
+/// events carry the name of the step as the key `step`, so that they can be matched in JSON logs.
+pub struct Step {
+    /// The logger the events are sent to.
+    logger: Logger,
+    /// What the step does, e.g. "Installing nns-dapp".
+    name: String,
+    /// When the step started.
+    started: Instant,
+}
+
+impl Step {
+    /// Logs the start of a step.
+    pub fn start(logger: &Logger, name: impl Into<String>) -> Step {
+        let name = name.into();
+        info!(logger, "{name}..."; "step" => &name, "event" => "start");
+        Step {
+            logger: logger.clone(),
+            name,
+            started: Instant::now(),
+        }
+    }
+
+    /// Logs the end of the step and how long it took.
+    pub fn finish(self) {
+        let duration_ms = self.started.elapsed().as_millis() as u64;
+        info!(
+            self.logger,
+            "{}: done in {:.1}s", self.name, duration_ms as f64 / 1000.0;
+            "step" => &self.name,
+            "event" => "finish",
+            "duration_ms" => duration_ms
+        );
+    }
 }
@@ -4,8 +4,6 @@ use std::path::PathBuf;
 
 const BINARY_DEPENDENCIES: &[(&str, &str)] = &[
     // (downloaded binary name, renamed binary name)
-    ("ic-nns-init", "ic-nns-init"),
-    ("ic-admin", "ic-admin"),
     ("sns", "sns-cli"),
 ];
 
@@ -8,6 +8,9 @@ assets="$(dirname "$BATS_TEST_FILENAME")"/../assets
 setup() {
     standard_setup
 
+    # No hashes are pinned yet for the release assets of the frontend canisters and the cycles ledger.
+    export DFX_ALLOW_UNVERIFIED_DOWNLOADS=1
+
     dfx_extension_install_manually nns
 
     dfx_new
@@ -21,25 +24,6 @@ teardown() {
     standard_teardown
 }
 
-@test "ic-nns-init binary exists and is executable" {
-    # it panics, but still shows help
-    run "$(dfx cache show)/extensions/nns/ic-nns-init" --help
-    assert_failure
-    assert_output --partial "thread 'main' panicked"
-    assert_output --partial "Illegal arguments:"
-    assert_output --partial "ic-nns-init [OPTIONS]"
-
-    # --version fails too
-    run "$(dfx cache show)/extensions/nns/ic-nns-init" --version
-    assert_failure
-}
-
-@test "ic-admin binary exists and is executable" {
-    run "$(dfx cache show)/extensions/nns/ic-admin" --help
-    assert_success
-    assert_output --partial 'Common command-line options for `ic-admin`'
-}
-
 @test "dfx nns install command exists" {
     run dfx nns install --help
     assert_success
@@ -60,13 +44,28 @@ nns_canister_id() {
     # Coming soon:
     #nns-ic-ckbtc-minter)   echo "qjdve-lqaaa-aaaaa-aaaeq-cai" ;;
     nns-sns-wasm)          echo "qaa6y-5yaaa-aaaaa-aaafa-cai" ;;
-    internet_identity)     echo "qhbym-qaaaa-aaaaa-aaafq-cai" ;;
+    internet_identity)     internet_identity_id ;;
     nns-dapp)              echo "qsgjb-riaaa-aaaaa-aaaga-cai" ;;
     *)                     echo "ERROR: Unknown NNS canister '$1'." >&2
                            exit 1;;
     esac
 }
 
+# The subnet of the given kind in the PocketIC topology, if there is one.
+topology_subnet() {
+    curl -s http://localhost:8080/_/topology | jq -r --arg kind "$1" '.subnet_configs | map_values(select(.subnet_kind==$kind)) | keys[]' | head -n 1
+}
+
+# Internet Identity has its mainnet ID if the local network has an II subnet.
+internet_identity_id() {
+    if [[ "$(topology_subnet II)" == "" ]]
+    then
+        echo "qhbym-qaaaa-aaaaa-aaafq-cai"
+    else
+        echo "rdmx6-jaaaa-aaaaa-aaadq-cai"
+    fi
+}
+
 assert_nns_canister_id_matches() {
     [[ "$(nns_canister_id "$1")" == "$(dfx canister id "$1")" ]] || {
        echo "ERROR: NNS canister ID mismatch for $1: $(nns_canister_id "$1") != $(dfx canister id "$1")"
@@ -174,7 +173,7 @@ assert_nns_canister_id_matches() {
     account_has_funds "$ED25519_ACCOUNT_ID"
 
     echo "    The Internet Identity and NNS dapp should load"
-    curl "http://qhbym-qaaaa-aaaaa-aaafq-cai.localhost:8080" | grep "<title>Internet Identity</title>"
+    curl "http://$(nns_canister_id internet_identity).localhost:8080" | grep "<title>Internet Identity</title>"
     curl "http://qsgjb-riaaa-aaaaa-aaaga-cai.localhost:8080" | gzip -d | grep "<title>NNS Dapp</title>"
 
     echo "    The secp256k1 account can be controlled from the command line"
@@ -202,6 +201,434 @@ assert_nns_canister_id_matches() {
     assert_output --partial "Canister um5iw-rqaaa-aaaaq-qaaba-cai not found"
 }
 
+@test "dfx nns install --dry-run prints the plan without installing anything" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+
+    run dfx nns install --dry-run
+    assert_success
+    assert_output --partial "NNS INSTALLATION PLAN"
+    assert_output --partial "Create and install the core NNS canisters"
+    assert_output --partial "nns-dapp"
+    assert_output --partial "function:  SetAuthorizedSubnetworks"
+    # The ICP index is installed after the NNS ledger.
+    assert_output --partial "after:   ryjl3-tyaaa-aaaaa-aaaba-cai"
+
+    run dfx nns install --dry-run --jobs 0
+    assert_failure
+
+    run dfx nns install --dry-run --only nns-icp-index
+    assert_success
+    assert_output --partial "Upload the SNS wasms to the nns-sns-wasm canister:
+  skipped"
+
+    run dfx nns install --dry-run --ic-commit 0123456789abcdef0123456789abcdef01234567
+    assert_success
+    assert_output --partial "IC commit: 0123456789abcdef0123456789abcdef01234567"
+
+    run dfx canister info "$(nns_canister_id nns-governance)"
+    refute_output --partial "Module hash: 0x"
+}
+
+@test "dfx nns install --output json prints the installed canisters" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+
+    dfx nns install --only nns-dapp --output json 2>progress.txt >install.json
+    run jq -r '.backend_canisters[] | select(.name == "nns-governance") | .canister_id' install.json
+    assert_success
+    assert_output "rrkah-fqaaa-aaaaa-aaaaq-cai"
+    run jq -r '.frontend_canisters[] | select(.name == "nns-dapp") | .url' install.json
+    assert_output --regexp "^http://qsgjb-riaaa-aaaaa-aaaga-cai.localhost:[0-9]+/$"
+    run jq -r '.backend_canisters[] | select(.name == "nns-ledger") | .module_hash' install.json
+    assert_output --regexp "^[0-9a-f]{64}$"
+    run jq -r '.test_accounts | length' install.json
+    assert_output "2"
+    run jq -r '.subnets.nns' install.json
+    assert_success
+    refute_output "null"
+    run cat progress.txt
+    assert_output --partial "Installing the core backend wasm canisters..."
+
+    run dfx nns install --dry-run --output json
+    assert_failure
+}
+
+@test "dfx nns install --icp-xdr-rate sets the conversion rate of the cycles minting canister" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+
+    run dfx nns install --dry-run --icp-xdr-rate 0.00001
+    assert_failure
+    assert_output --partial "at most four decimal places"
+
+    dfx nns install --profile minimal --icp-xdr-rate 12.5
+
+    run dfx canister call "$(nns_canister_id nns-cycles-minting)" get_icp_xdr_conversion_rate '()' --query
+    assert_success
+    assert_output --partial "xdr_permyriad_per_icp = 125_000"
+}
+
+@test "dfx nns install --neuron creates neurons for principals and identities" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+    dfx identity new --storage-mode plaintext alice
+    ALICE="$(dfx identity get-principal --identity alice)"
+
+    dfx nns install --profile minimal --neuron 'alice,stake=100,dissolve_delay=252460800,name=Alice' --neuron '2vxsx-fae,maturity=5,follow=all:1000'
+
+    run dfx canister call "$(nns_canister_id nns-governance)" get_neuron_info '(1000 : nat64)' --query
+    assert_success
+    assert_output --partial "stake_e8s = 10_000_000_000"
+    assert_output --partial "dissolve_delay_seconds = 252_460_800"
+    assert_output --partial 'name = "Alice"'
+
+    run dfx canister call "$(nns_canister_id nns-governance)" list_known_neurons '()' --query
+    assert_success
+    assert_output --partial '"Alice"'
+
+    run dfx canister call "$(nns_canister_id nns-governance)" get_full_neuron '(1000 : nat64)' --identity alice
+    assert_success
+    assert_output --partial "${ALICE}"
+
+    run dfx canister call "$(nns_canister_id nns-governance)" get_neuron_info '(1001 : nat64)' --query
+    assert_success
+}
+
+@test "dfx nns ledger shows and moves ICP" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+    dfx nns install --profile core
+    install_asset nns
+    dfx identity import --force --disable-encryption ident-1 ident-1/identity.pem
+    dfx identity new --storage-mode plaintext alice
+    ALICE="$(dfx identity get-principal --identity alice)"
+    ALICE_ACCOUNT_ID="$(dfx ledger account-id --identity alice)"
+
+    run dfx nns ledger balance 2b8fbde99de881f695f279d2a892b1137bfe81a42d7694e064b1be58701e1138
+    assert_success
+    assert_output "1000000000.00000000 ICP"
+
+    run dfx nns ledger transfer "$ALICE" --amount 12.5 --identity ident-1
+    assert_success
+    assert_output --partial "Transferred 12.50000000 ICP to ${ALICE}"
+
+    run dfx nns ledger balance --identity alice
+    assert_success
+    assert_output "12.50000000 ICP"
+    run dfx nns ledger balance "$ALICE_ACCOUNT_ID"
+    assert_output "12.50000000 ICP"
+
+    run dfx nns ledger approve "$ALICE" --amount 3 --identity ident-1
+    assert_success
+    run dfx nns ledger approve "$ALICE_ACCOUNT_ID" --amount 3 --identity ident-1
+    assert_failure
+    assert_output --partial "The spender must be a principal or an ICRC-1 account"
+
+    run dfx nns ledger balance not-an-account
+    assert_failure
+
+    sleep 5 # The index canister syncs with the ledger periodically.
+    run dfx nns ledger history --identity alice
+    assert_success
+    assert_output --regexp "transfer +12.50000000 ICP"
+}
+
+@test "dfx nns neuron stakes and manages neurons" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+    dfx nns install --profile minimal
+    install_asset nns
+    dfx identity import --force --disable-encryption ident-1 ident-1/identity.pem
+
+    run dfx nns neuron stake --identity ident-1 --amount 5 --memo 7
+    assert_success
+    assert_output --regexp "Staked 5.00000000 ICP in neuron [0-9]+."
+    NEURON_ID="$(echo "$output" | sed -E 's/.* neuron ([0-9]+)\./\1/')"
+
+    run dfx nns neuron list --identity ident-1
+    assert_success
+    assert_output --regexp "${NEURON_ID} +locked +5.00000000 ICP"
+
+    run dfx nns neuron increase-dissolve-delay "$NEURON_ID" --by 15778800 --identity ident-1
+    assert_success
+    run dfx nns neuron split "$NEURON_ID" --amount 2 --identity ident-1
+    assert_success
+    assert_output --regexp "to the new neuron [0-9]+."
+    NEW_NEURON_ID="$(echo "$output" | sed -E 's/.* neuron ([0-9]+)\./\1/')"
+    run dfx nns neuron follow "$NEURON_ID" --topic governance --followee "$NEW_NEURON_ID" --identity ident-1
+    assert_success
+
+    run dfx nns neuron start-dissolving "$NEURON_ID" --identity ident-1
+    assert_success
+    run dfx nns neuron list --identity ident-1
+    assert_output --regexp "${NEURON_ID} +dissolving"
+    run dfx nns neuron stop-dissolving "$NEURON_ID" --identity ident-1
+    assert_success
+
+    run dfx nns neuron disburse "$NEURON_ID" --identity ident-1
+    assert_failure
+}
+
+@test "dfx nns propose submits proposals that the test neuron adopts" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+    dfx nns install --profile minimal
+
+    run dfx nns propose motion --text "Let there be light" --title "A test motion"
+    assert_success
+    assert_output --regexp "Proposal [0-9]+ was executed."
+
+    run dfx nns propose network-economics --neuron-minimum-stake 2
+    assert_success
+    run dfx canister call "$(nns_canister_id nns-governance)" get_network_economics_parameters '()' --query
+    assert_success
+    assert_output --partial "neuron_minimum_stake_e8s = 200_000_000"
+
+    NODE_PROVIDER="$(dfx identity get-principal)"
+    run dfx nns propose add-node-provider "$NODE_PROVIDER"
+    assert_success
+    run dfx canister call "$(nns_canister_id nns-governance)" list_node_providers '()' --query
+    assert_output --partial "$NODE_PROVIDER"
+    run dfx nns propose remove-node-provider "$NODE_PROVIDER"
+    assert_success
+
+    run dfx nns propose network-economics
+    assert_failure
+    assert_output --partial "Pass at least one network economics parameter"
+}
+
+@test "dfx nns upgrade upgrades an NNS canister with a proposal" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+    dfx nns install --profile minimal
+
+    WASM_DIR="$DFX_CACHE_ROOT/.cache/dfinity/versions/$(dfx --version | awk '{printf "%s", $2}')/wasms"
+    run dfx nns upgrade nns-governance --wasm "$WASM_DIR/governance-canister_test.wasm"
+    assert_success
+    assert_output --partial "nns-governance already runs the wasm"
+
+    # The same module, compressed differently, has a different hash.
+    gunzip -c "$WASM_DIR/governance-canister_test.wasm" | gzip -1 -n >governance.wasm.gz
+    NEW_HASH="0x$(sha256sum governance.wasm.gz | awk '{print $1}')"
+    run dfx nns upgrade governance --wasm governance.wasm.gz
+    assert_success
+    assert_output --regexp "Submitted proposal [0-9]+ to upgrade nns-governance."
+    assert_output --partial "to ${NEW_HASH}."
+    run dfx canister info "$(nns_canister_id nns-governance)"
+    assert_output --partial "Module hash: ${NEW_HASH}"
+    # The upgrade is recorded, so the canister is not reported as drifted.
+    run dfx nns status
+    assert_output --regexp "nns-governance +rrkah-fqaaa-aaaaa-aaaaq-cai +matching"
+
+    run dfx nns upgrade nns-unknown --wasm governance.wasm.gz
+    assert_failure
+    assert_output --partial "'nns-unknown' is not a core NNS canister."
+}
+
+@test "dfx nns install places canisters on the subnets of the local network" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+
+    II_SUBNET_ID="$(topology_subnet II)"
+    APP_SUBNET_ID="$(topology_subnet Application)"
+    if [[ "${II_SUBNET_ID}" == "" || "${APP_SUBNET_ID}" == "" ]]
+    then
+      echo "The PocketIC instance topology has no II or application subnet."
+      exit 1
+    fi
+    run dfx nns install --dry-run --with-cycles-ledger --app-subnet "${APP_SUBNET_ID}"
+    assert_success
+    assert_output --partial "internet_identity     rdmx6-jaaaa-aaaaa-aaadq-cai"
+    assert_output --partial "subnet:  ${II_SUBNET_ID}"
+    assert_output --partial "nns-icrc1-ledger      ss2fx-dyaaa-aaaar-qacoq-cai"
+    assert_output --partial "on subnets ${APP_SUBNET_ID} with a proposal"
+
+    run dfx nns install --dry-run --app-subnet aaaaa-aa
+    assert_failure
+    assert_output --partial "Subnet aaaaa-aa is not part of the local network."
+}
+
+@test "dfx nns install can be run again" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+
+    dfx nns install
+    run dfx nns install
+    assert_success
+    assert_output --partial "The core backend canisters are already installed."
+    assert_output --partial "Already installed: nns-dapp"
+
+    run dfx nns install --force-reinstall nns-dapp
+    assert_success
+    assert_output --partial "Installing nns-dapp: done"
+
+    run dfx nns install --force-reinstall nns-governance
+    assert_failure
+    assert_output --partial "Cannot reinstall 'nns-governance'"
+}
+
+@test "dfx nns install reports progress through the logger" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+
+    dfx nns install
+    run dfx nns install --quiet
+    assert_success
+    refute_output --partial "Already installed: nns-dapp"
+
+    dfx nns install --force-reinstall nns-dapp --log-format json 2>progress.json
+    run jq -rR 'fromjson? | select(.step == "Installing nns-dapp" and .event == "finish") | "\(.canister) \(.duration_ms)"' progress.json
+    assert_success
+    assert_output --regexp "^nns-dapp [0-9]+$"
+}
+
+@test "dfx nns install --wasm-dir lists every missing wasm" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+
+    mkdir wasms
+    touch wasms/registry-canister_test.wasm
+    run dfx nns install --wasm-dir wasms
+    assert_failure
+    assert_output --partial "wasms/governance-canister_test.wasm"
+    assert_output --partial "wasms/internet_identity_dev.wasm"
+    refute_output --partial "wasms/registry-canister_test.wasm"
+    refute_output --partial "Downloading"
+}
+
+@test "dfx nns install --profile minimal installs only the core canisters" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+
+    dfx nns install --profile minimal
+    run dfx nns status
+    assert_success
+    assert_output --regexp "nns-ledger +ryjl3-tyaaa-aaaaa-aaaba-cai +matching"
+    assert_output --regexp "nns-dapp +qsgjb-riaaa-aaaaa-aaaga-cai +missing"
+    assert_output --regexp "nns-icp-index +q3fc5-haaaa-aaaaa-aaahq-cai +missing"
+
+    run dfx nns install --skip nns-governance
+    assert_failure
+    assert_output --partial "cannot be chosen individually"
+}
+
+@test "dfx nns install --with-cycles-ledger installs the cycles ledger" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+
+    run dfx nns install --profile minimal --with-cycles-ledger
+    assert_success
+    assert_output --regexp "cycles_ledger +um5iw-rqaaa-aaaaq-qaaba-cai"
+
+    run dfx canister call um5iw-rqaaa-aaaaq-qaaba-cai icrc1_symbol '()' --query
+    assert_success
+    assert_output --partial "TCYCLES"
+}
+
+@test "dfx nns install --with-ckbtc installs the ckBTC canisters" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+
+    run dfx nns install --profile minimal --with-ckbtc
+    assert_success
+    assert_output --regexp "ckbtc_minter +mqygn-kiaaa-aaaar-qaadq-cai"
+
+    run dfx canister call mxzaz-hqaaa-aaaar-qaada-cai icrc1_symbol '()' --query
+    assert_success
+    assert_output --partial "ckBTC"
+
+    run dfx canister call mxzaz-hqaaa-aaaar-qaada-cai icrc1_minting_account '()' --query
+    assert_success
+    assert_output --partial "mqygn-kiaaa-aaaar-qaadq-cai"
+}
+
+@test "dfx nns install --test-token installs the declared ICRC-1 ledgers" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+
+    run dfx nns install --profile core --test-token "TKN,name=Test Token,decimals=6,fee=100,balance=2vxsx-fae:1000000" --test-token "XYZ,index=false"
+    assert_success
+    assert_output --regexp "tkn-ledger +3wht4-pqaaa-aaaaa-acaaa-cai"
+    assert_output --regexp "tkn-index +3rgvi-ciaaa-aaaaa-acaaq-cai"
+    assert_output --regexp "xyz-ledger +3yf6u-uaaaa-aaaaa-acaba-cai"
+    refute_output --partial "xyz-index"
+    refute_output --partial "nns-icrc1-ledger"
+
+    run dfx canister call 3wht4-pqaaa-aaaaa-acaaa-cai icrc1_decimals '()' --query
+    assert_success
+    assert_output --partial "6"
+
+    run dfx canister call 3wht4-pqaaa-aaaaa-acaaa-cai icrc1_balance_of '(record { owner = principal "2vxsx-fae" })' --query
+    assert_success
+    assert_output --partial "1_000_000"
+
+    run dfx canister call 3yf6u-uaaaa-aaaaa-acaba-cai icrc1_symbol '()' --query
+    assert_success
+    assert_output --partial "XYZ"
+}
+
+@test "dfx nns install reads its settings from nns.json" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+
+    cat <<EOF >nns.json
+{
+  "profile": "minimal",
+  "with_cycles_ledger": true,
+  "nns_dapp_feature_flags": { "ENABLE_HIDE_ZERO_BALANCE": false }
+}
+EOF
+    run dfx nns install --dry-run
+    assert_success
+    assert_output --partial "cycles_ledger"
+    refute_output --regexp "nns-dapp +qsgjb-riaaa-aaaaa-aaaga-cai"
+
+    run dfx nns install --dry-run --profile full
+    assert_success
+    assert_output --partial 'ENABLE_HIDE_ZERO_BALANCE\":false'
+
+    echo '{ "unknown_setting": true }' >nns.json
+    run dfx nns install --dry-run
+    assert_failure
+    assert_output --partial "unknown_setting"
+}
+
+@test "dfx nns install refuses wasms without a known hash" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+
+    echo '{ "frontend_wasm_urls": { "nns-dapp": "http://localhost:1/nns-dapp_test.wasm.gz" } }' >nns.json
+    DFX_ALLOW_UNVERIFIED_DOWNLOADS=0 run dfx nns install
+    assert_failure
+    assert_output --partial "No SHA-256 hash is pinned"
+    assert_output --partial "DFX_ALLOW_UNVERIFIED_DOWNLOADS=1"
+
+    DFX_ALLOW_UNVERIFIED_DOWNLOADS=yes run dfx nns install
+    assert_failure
+    assert_output --partial "DFX_ALLOW_UNVERIFIED_DOWNLOADS must be 0 or 1, not 'yes'."
+}
+
+@test "dfx nns status shows which canisters are installed" {
+    install_shared_asset subnet_type/shared_network_settings/system
+    dfx_start_for_nns_install
+
+    run dfx nns status
+    assert_success
+    assert_output --regexp "nns-governance +rrkah-fqaaa-aaaaa-aaaaq-cai +missing"
+
+    dfx nns install
+    run dfx nns status
+    assert_success
+    assert_output --regexp "Installed from IC commit [0-9a-f]{40}."
+    assert_output --regexp "nns-governance +rrkah-fqaaa-aaaaa-aaaaq-cai +matching"
+    assert_output --regexp "nns-dapp +qsgjb-riaaa-aaaaa-aaaga-cai +matching"
+
+    run dfx nns status --output json
+    assert_success
+    assert_output --partial '"status": "matching"'
+}
+
 @test "dfx nns install with a canister type defined by another extension" {
     install_shared_asset subnet_type/shared_network_settings/system
     dfx_start_for_nns_install
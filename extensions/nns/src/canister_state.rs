@@ -0,0 +1,94 @@
+//! Inspects which canisters are already present on the local network.
+#![warn(missing_docs)]
+#![warn(clippy::missing_docs_in_private_items)]
+
+use anyhow::Context;
+use fn_error_context::context;
+use ic_agent::export::Principal;
+use ic_agent::{Agent, AgentError};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+/// What is found at a canister ID on the network.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CanisterState {
+    /// There is no canister with this ID.
+    Missing,
+    /// The canister exists but no wasm module is installed.
+    Empty,
+    /// The canister runs a wasm module with this hash.
+    Installed {
+        /// The SHA-256 hash of the installed module.
+        module_hash: Vec<u8>,
+    },
+}
+
+impl CanisterState {
+    /// Whether the canister has been created, with or without a module.
+    pub fn exists(&self) -> bool {
+        !matches!(self, CanisterState::Missing)
+    }
+
+    /// Whether the canister runs exactly the given module.
+    pub fn runs(&self, expected_hash: &[u8]) -> bool {
+        matches!(self, CanisterState::Installed { module_hash } if module_hash == expected_hash)
+    }
+}
+
+/// Reads the state of a canister from the network.
+///
+/// The network is queried directly; local state such as canister_ids.json has no effect on this function.
+///
+/// # Errors
+/// - Returns an error if the network cannot be reached or its answer cannot be verified.  Only an answer that the
+///   canister or its module does not exist is taken to mean `Missing` or `Empty`.
+#[context("Failed to read the state of canister {}.", canister_id)]
+pub async fn canister_state(
+    agent: &Agent,
+    canister_id: Principal,
+) -> anyhow::Result<CanisterState> {
+    // Every canister has controllers, even if no module has been installed yet.
+    match agent
+        .read_state_canister_info(canister_id, "controllers")
+        .await
+    {
+        Ok(_) => {}
+        Err(err) if is_not_found(&err) => return Ok(CanisterState::Missing),
+        Err(err) => return Err(err.into()),
+    }
+    match agent
+        .read_state_canister_info(canister_id, "module_hash")
+        .await
+    {
+        Ok(module_hash) => Ok(CanisterState::Installed { module_hash }),
+        Err(err) if is_not_found(&err) => Ok(CanisterState::Empty),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Whether a `read_state` error says that the path does not exist, rather than that the network could not answer.
+///
+/// The certificate leaves out the paths of canisters that do not exist on the subnet, and the replica rejects the
+/// request if no subnet has the canister ID.
+pub fn is_not_found(err: &AgentError) -> bool {
+    match err {
+        AgentError::LookupPathAbsent(_) => true,
+        AgentError::HttpError(payload) => {
+            matches!(payload.status, 400 | 404)
+                && String::from_utf8_lossy(&payload.content)
+                    .to_lowercase()
+                    .contains("not found")
+        }
+        _ => false,
+    }
+}
+
+/// Computes the SHA-256 hash of a file, such as a wasm module.
+///
+/// For wasm modules this is the hash reported by the network once the module is installed.
+#[context("Failed to compute the hash of '{}'.", path.display())]
+pub fn file_sha256(path: &Path) -> anyhow::Result<Vec<u8>> {
+    let bytes = fs::read(path).with_context(|| format!("Unable to read {:?}", path))?;
+    Ok(Sha256::digest(bytes).to_vec())
+}
@@ -7,11 +7,13 @@ use dfx_core::config::model::dfinity::Config;
 use dfx_core::extension::manager::ExtensionManager;
 use dfx_extensions_utils::{
     dependencies::dfx::NNS_SNS_REPLICA_REV, get_canisters_json_object, get_network_mappings,
-    import_canister_definitions, new_logger, set_remote_canister_ids, ImportNetworkMapping,
-    NNS_CORE,
+    import_canister_definitions, set_remote_canister_ids, ImportNetworkMapping, NNS_CORE,
 };
 
+use crate::install_state::InstallState;
+
 use clap::Parser;
+use dfx_core::DfxInterfaceBuilder;
 use slog::{info, Logger};
 
 /// Imports the nns canisters
@@ -25,10 +27,15 @@ pub struct ImportOpts {
     ///   --network-mapping ic=mainnet
     #[clap(long, default_value = "ic=mainnet", action = clap::ArgAction::Append)]
     network_mapping: Vec<String>,
+
+    /// IC commit of the NNS canister definitions to import.  Defaults to the commit that `dfx nns install` installed
+    /// on the local network, if it is running.
+    #[arg(long, env = "DFX_IC_COMMIT")]
+    ic_commit: Option<String>,
 }
 
 /// Executes `dfx nns import`
-pub async fn exec(opts: ImportOpts, dfx_cache_path: &Path) -> anyhow::Result<()> {
+pub async fn exec(opts: ImportOpts, dfx_cache_path: &Path, logger: &Logger) -> anyhow::Result<()> {
     let version = get_version_from_cache_path(dfx_cache_path)?;
     let extension_manager = ExtensionManager::new(&version)?;
     let config = Config::from_current_dir(Some(&extension_manager))?;
@@ -36,10 +43,17 @@ pub async fn exec(opts: ImportOpts, dfx_cache_path: &Path) -> anyhow::Result<()>
         anyhow::bail!(crate::errors::DFXJSON_NOT_FOUND);
     }
     let mut config = config.unwrap().clone();
-    let logger = new_logger();
 
     let network_mappings = get_network_mappings(&opts.network_mapping)?;
-    let ic_commit = std::env::var("DFX_IC_COMMIT").unwrap_or(NNS_SNS_REPLICA_REV.to_string());
+    let install_state = local_install_state(dfx_cache_path).await?;
+    let ic_commit = opts
+        .ic_commit
+        .or_else(|| {
+            install_state
+                .as_ref()
+                .map(|install_state| install_state.ic_commit.clone())
+        })
+        .unwrap_or(NNS_SNS_REPLICA_REV.to_string());
     let dfx_url_str = {
         let ic_project = std::env::var("DFX_IC_SRC").unwrap_or_else(|_| {
             format!("https://raw.githubusercontent.com/dfinity/ic/{ic_commit}")
@@ -47,7 +61,7 @@ pub async fn exec(opts: ImportOpts, dfx_cache_path: &Path) -> anyhow::Result<()>
         format!("{ic_project}/rs/nns/dfx.json")
     };
     import_canister_definitions(
-        &logger,
+        logger,
         &mut config,
         &dfx_url_str,
         Some("nns-"),
@@ -56,19 +70,60 @@ pub async fn exec(opts: ImportOpts, dfx_cache_path: &Path) -> anyhow::Result<()>
     )
     .await?;
 
-    set_local_nns_canister_ids(&logger, &mut config)
+    set_local_nns_canister_ids(logger, &mut config, install_state.as_ref())
+}
+
+/// Reads what `dfx nns install` recorded for the local network.
+///
+/// The record belongs to the running network, so there is none if the local network is not running.
+async fn local_install_state(dfx_cache_path: &Path) -> anyhow::Result<Option<InstallState>> {
+    let Ok(dfx) = DfxInterfaceBuilder::new()
+        .anonymous()
+        .with_extension_manager_from_cache_path(dfx_cache_path)?
+        .build()
+        .await
+    else {
+        return Ok(None);
+    };
+    InstallState::load(dfx.network_descriptor())
 }
 
 /// Sets local canister IDs
 /// The "local" entries at the remote URL are often missing or do not match our NNS installation.
-/// Always set the local values per our local NNS deployment.  We have all the information locally.
-fn set_local_nns_canister_ids(logger: &Logger, config: &mut Config) -> anyhow::Result<()> {
-    let local_canister_ids: CanisterIds = NNS_CORE
+/// Always set the local values per our local NNS deployment, as recorded by `dfx nns install` or else the defaults.
+/// We have all the information locally.
+fn set_local_nns_canister_ids(
+    logger: &Logger,
+    config: &mut Config,
+    install_state: Option<&InstallState>,
+) -> anyhow::Result<()> {
+    let local_ids: Vec<(String, String)> = match install_state {
+        Some(install_state) => install_state
+            .canisters
+            .iter()
+            .map(|canister| {
+                (
+                    canister.canister_name.clone(),
+                    canister.canister_id.to_string(),
+                )
+            })
+            .collect(),
+        None => NNS_CORE
+            .iter()
+            .map(|canister| {
+                (
+                    canister.canister_name.to_string(),
+                    canister.canister_id.to_string(),
+                )
+            })
+            .collect(),
+    };
+    let local_canister_ids: CanisterIds = local_ids
         .iter()
-        .map(|canister| {
+        .map(|(canister_name, canister_id)| {
             (
-                canister.canister_name.to_string(),
-                BTreeMap::from([("local".to_string(), canister.canister_id.to_string())]),
+                canister_name.clone(),
+                BTreeMap::from([("local".to_string(), canister_id.clone())]),
             )
         })
         .collect();
@@ -79,25 +134,22 @@ fn set_local_nns_canister_ids(logger: &Logger, config: &mut Config) -> anyhow::R
 
     let canisters = get_canisters_json_object(config)?;
 
-    for canister in NNS_CORE {
+    for (canister_name, _) in &local_ids {
         // Not all NNS canisters may be listed in the remote dfx.json
         let dfx_canister = canisters
-            .get_mut(canister.canister_name)
+            .get_mut(canister_name)
             .and_then(|canister_entry| canister_entry.as_object_mut());
         // If the canister is in dfx.json, set the local canister ID.
         if let Some(dfx_canister) = dfx_canister {
             set_remote_canister_ids(
                 logger,
-                canister.canister_name,
+                canister_name,
                 &local_mappings,
                 &local_canister_ids,
                 dfx_canister,
             )?;
         } else {
-            info!(
-                logger,
-                "{} has no local canister ID.", canister.canister_name
-            );
+            info!(logger, "{} has no local canister ID.", canister_name);
         }
     }
     config.save()?;
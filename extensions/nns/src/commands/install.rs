@@ -1,9 +1,17 @@
 //! Code for the command line: `dfx nns install`
-use crate::install_nns::{get_and_check_replica_url, get_with_retries, install_nns};
+use crate::commands::OutputFormat;
+use crate::install_config::InstallConfig;
+use crate::install_nns::{get_and_check_replica_url, get_with_retries, install_nns, BtcNetwork};
+use crate::install_plan::InstallProfile;
+use crate::neurons::NeuronSpec;
+use crate::test_tokens::TestToken;
 use clap::Parser;
 use dfx_core::DfxInterfaceBuilder;
-use dfx_extensions_utils::new_logger;
-use std::path::Path;
+use ic_agent::export::Principal;
+use rust_decimal::Decimal;
+use slog::Logger;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 
 /// Installs the NNS canisters, Internet Identity and the NNS frontend dapp
 ///
@@ -14,20 +22,122 @@ use std::path::Path;
 ///
 /// - internet_identity is a login service.
 ///   Source code is at <https://github.com/dfinity/internet-identity>.
-///   This frontend is typically served at: <http://qaa6y-5yaaa-aaaaa-aaafa-cai.localhost:8080>.
+///   This frontend is served by the local network, e.g. at: <http://rdmx6-jaaaa-aaaaa-aaadq-cai.localhost:8080>.
 ///
 /// - nns-dapp is a voting app and wallet. Source code is at <https://github.com/dfinity/nns-dapp>.
-///   This frontend is typically served at: <http://qhbym-qaaaa-aaaaa-aaafq-cai.localhost:8080>.
+///   This frontend is served by the local network, e.g. at: <http://qsgjb-riaaa-aaaaa-aaaga-cai.localhost:8080>.
+///
+/// - cycles_ledger holds cycles balances and is installed with `--with-cycles-ledger`.
+///   Source code is at <https://github.com/dfinity/cycles-ledger>.
+///
+/// - ckbtc_ledger, ckbtc_index and ckbtc_minter are installed at their mainnet IDs with `--with-ckbtc`.
+///   The minter uses the bitcoin canister of the network chosen with `--ckbtc-network`.
+///   Source code is at <https://github.com/dfinity/ic/tree/master/rs/bitcoin/ckbtc>.
+///
+/// - ICRC-1 test tokens declared with `--test-token` or `--test-tokens-file` replace the ckETH ledger and index.
+///   The ledger of the n-th token (counting from 0) gets the canister ID with index 0x1000 + 2n in the NNS subnet
+///   range and its index canister the ID after that.
+///
+/// Canisters are placed on the subnets mainnet puts them on, if the local network has those subnets: Internet Identity
+/// and the cycles ledger on the II subnet, the ckBTC and ckETH canisters on the fiduciary subnet and the rest on the NNS
+/// subnet.  Internet Identity then has its mainnet ID, and the ckETH ledger and index have theirs.
+/// The cycles minting canister is authorized to create canisters on all application subnets.
+///
+/// - NNS neurons declared with `--neuron` are created with the governance canister, with IDs counting up from 1000.
+///
+/// Settings can also be given in an `nns.json` file next to `dfx.json`, so that a whole team gets the same local NNS.
+///
+/// Canisters that are already installed are left alone, so an interrupted installation can be completed by running
+/// the command again.
+///
+/// What was installed, including the IC commit, the wasm hashes, the canister IDs, the test accounts and the frontend
+/// URLs, is recorded in the data directory of the local network for later `dfx nns` commands.
 #[derive(Parser)]
 #[clap(about)]
 pub struct InstallOpts {
     /// Initialize ledger canister with these test accounts
     #[arg(long, action = clap::ArgAction::Append, num_args = 0..)]
-    ledger_accounts: Vec<String>,
+    pub ledger_accounts: Vec<String>,
+
+    /// Install the NNS wasms built at this commit of the IC repository
+    #[arg(long, env = "DFX_IC_COMMIT")]
+    pub ic_commit: Option<String>,
+
+    /// Print the installation plan without changing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Print the installed canisters as a `table` or as `json`; progress messages go to stderr
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table, conflicts_with = "dry_run")]
+    pub output: OutputFormat,
+
+    /// Reinstall this canister even if it already runs a different wasm
+    #[arg(long, action = clap::ArgAction::Append)]
+    pub force_reinstall: Vec<String>,
+
+    /// Install the wasms in this directory instead of downloading them
+    #[arg(long)]
+    pub wasm_dir: Option<PathBuf>,
+
+    /// Which NNS components to install [default: full]
+    #[arg(long, value_enum)]
+    pub profile: Option<InstallProfile>,
+
+    /// Do not install this canister, or do not upload the SNS wasms with `sns-wasms`
+    #[arg(long, action = clap::ArgAction::Append)]
+    pub skip: Vec<String>,
+
+    /// Install only this canister besides the core NNS canisters, or upload the SNS wasms with `sns-wasms`
+    #[arg(long, action = clap::ArgAction::Append, conflicts_with_all = ["profile", "skip"])]
+    pub only: Vec<String>,
+
+    /// Also install the cycles ledger
+    #[arg(long)]
+    pub with_cycles_ledger: bool,
+
+    /// Also install the ckBTC ledger, index and minter
+    #[arg(long)]
+    pub with_ckbtc: bool,
+
+    /// The bitcoin network used by the ckBTC minter [default: regtest]
+    #[arg(long, value_enum)]
+    pub ckbtc_network: Option<BtcNetwork>,
+
+    /// Create an NNS neuron for this principal or dfx identity
+    ///
+    /// The format is OWNER[,stake=ICP][,dissolve_delay=SECONDS][,maturity=ICP][,name=NAME][,follow=TOPIC:NEURON_ID]...
+    #[arg(long, value_name = "SPEC", action = clap::ArgAction::Append)]
+    pub neuron: Vec<NeuronSpec>,
+
+    /// Install this ICRC-1 test token instead of the ckETH ledger
+    ///
+    /// The format is SYMBOL[,name=NAME][,decimals=N][,fee=N][,balance=PRINCIPAL:AMOUNT]...[,index=false]
+    #[arg(long, value_name = "SPEC", action = clap::ArgAction::Append)]
+    pub test_token: Vec<TestToken>,
+
+    /// Install the ICRC-1 test tokens declared in this JSON file instead of the ckETH ledger
+    #[arg(long)]
+    pub test_tokens_file: Option<PathBuf>,
+
+    /// Create SNS canisters in this subnet instead of the SNS subnet of the local network
+    #[arg(long, value_name = "SUBNET")]
+    pub sns_subnet: Option<Principal>,
+
+    /// Let the cycles minting canister create canisters in this subnet instead of the application subnets
+    #[arg(long, value_name = "SUBNET", action = clap::ArgAction::Append)]
+    pub app_subnet: Vec<Principal>,
+
+    /// Set the ICP/XDR conversion rate of the cycles minting canister, in XDR per ICP, e.g. 10.5
+    #[arg(long, value_name = "RATE")]
+    pub icp_xdr_rate: Option<Decimal>,
+
+    /// Download and install at most this many canisters at a time
+    #[arg(long, value_name = "N", default_value = "4")]
+    pub jobs: NonZeroUsize,
 }
 
 /// Executes `dfx nns install`.
-pub async fn exec(opts: InstallOpts, dfx_cache_path: &Path) -> anyhow::Result<()> {
+pub async fn exec(opts: InstallOpts, dfx_cache_path: &Path, logger: &Logger) -> anyhow::Result<()> {
     let dfx = DfxInterfaceBuilder::new()
         .anonymous()
         .with_extension_manager_from_cache_path(dfx_cache_path)?
@@ -38,15 +148,14 @@ pub async fn exec(opts: InstallOpts, dfx_cache_path: &Path) -> anyhow::Result<()
         local_server_descriptor.load_settings_digest()?;
     }
 
-    let logger = new_logger();
-
-    let config = dfx.config();
-    if config.is_none() {
+    let Some(config) = dfx.config() else {
         anyhow::bail!(crate::errors::DFXJSON_NOT_FOUND);
-    }
+    };
+    let mut install_config = InstallConfig::load(config.get_project_root())?;
+    install_config.apply_command_line(&opts)?;
 
     // Wait for the server to be ready...
-    let nns_url = get_and_check_replica_url(&network_descriptor, &logger)?;
+    let nns_url = get_and_check_replica_url(&network_descriptor, logger)?;
     get_with_retries(&nns_url).await?;
 
     install_nns(
@@ -54,8 +163,9 @@ pub async fn exec(opts: InstallOpts, dfx_cache_path: &Path) -> anyhow::Result<()
         &network_descriptor,
         dfx.networks_config(),
         dfx_cache_path,
-        &opts.ledger_accounts,
-        &logger,
+        &opts,
+        &install_config,
+        logger,
     )
     .await
 }
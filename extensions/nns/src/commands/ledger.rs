@@ -0,0 +1,185 @@
+//! Code for the command line: `dfx nns ledger`
+use crate::connection::ConnectionOpts;
+use crate::ledger::{self, Account, Operation, TransactionWithId};
+use crate::nns_types::account_identifier::{AccountIdentifier, Subaccount};
+use crate::nns_types::icpts::{ICPTs, TRANSACTION_FEE};
+
+use anyhow::anyhow;
+use clap::{Parser, Subcommand};
+use ic_agent::Agent;
+use std::path::Path;
+
+/// Shows and moves ICP on the NNS ledger
+///
+/// Accounts can be given as hex account identifiers, principals or ICRC-1 textual accounts.  The commands act as the
+/// dfx identity given with `--identity`, or the selected identity.
+#[derive(Parser)]
+#[clap(about)]
+pub struct LedgerOpts {
+    /// `dfx nns ledger` subcommand arguments.
+    #[clap(subcommand)]
+    subcmd: LedgerCommand,
+
+    /// The identity and network.
+    #[command(flatten)]
+    connection: ConnectionOpts,
+}
+
+/// Subcommands of `dfx nns ledger`.
+#[derive(Subcommand)]
+enum LedgerCommand {
+    /// Show the balance of an account.
+    Balance(BalanceOpts),
+    /// Transfer ICP from an account of the identity.
+    Transfer(TransferOpts),
+    /// Let another account spend ICP from an account of the identity.
+    Approve(ApproveOpts),
+    /// Show the transactions of an account, newest first.
+    History(HistoryOpts),
+}
+
+/// Arguments of `dfx nns ledger balance`.
+#[derive(Parser)]
+struct BalanceOpts {
+    /// The account.  Defaults to the main account of the identity.
+    account: Option<Account>,
+}
+
+/// Arguments of `dfx nns ledger transfer`.
+#[derive(Parser)]
+struct TransferOpts {
+    /// The receiving account.
+    to: Account,
+
+    /// The amount of ICP to transfer, e.g. 1.5.
+    #[arg(long)]
+    amount: ICPTs,
+
+    /// The memo of the transfer.
+    #[arg(long, default_value_t = 0)]
+    memo: u64,
+
+    /// The fee in ICP.  Defaults to the standard ledger fee.
+    #[arg(long)]
+    fee: Option<ICPTs>,
+
+    /// The subaccount of the identity to transfer from, in hex.  Defaults to the main account.
+    #[arg(long)]
+    from_subaccount: Option<Subaccount>,
+}
+
+/// Arguments of `dfx nns ledger approve`.
+#[derive(Parser)]
+struct ApproveOpts {
+    /// The account that may spend the ICP: a principal or an ICRC-1 account.
+    spender: Account,
+
+    /// The amount of ICP the spender may transfer, e.g. 1.5.
+    #[arg(long)]
+    amount: ICPTs,
+
+    /// When the allowance expires, in nanoseconds since the UNIX epoch.  Defaults to never.
+    #[arg(long, value_name = "NANOSECONDS")]
+    expires_at: Option<u64>,
+
+    /// The subaccount of the identity to spend from, in hex.  Defaults to the main account.
+    #[arg(long)]
+    from_subaccount: Option<Subaccount>,
+}
+
+/// Arguments of `dfx nns ledger history`.
+#[derive(Parser)]
+struct HistoryOpts {
+    /// The account.  Defaults to the main account of the identity.
+    account: Option<Account>,
+
+    /// The maximum number of transactions to show.
+    #[arg(long, default_value_t = 20)]
+    limit: u64,
+
+    /// Show transactions from this ledger block back.  Defaults to the newest transaction.
+    #[arg(long, value_name = "BLOCK")]
+    start: Option<u64>,
+}
+
+/// Executes `dfx nns ledger`.
+pub async fn exec(opts: LedgerOpts, dfx_cache_path: &Path) -> anyhow::Result<()> {
+    let dfx = opts.connection.dfx_interface(dfx_cache_path).await?;
+    let agent = dfx.agent();
+    match opts.subcmd {
+        LedgerCommand::Balance(opts) => {
+            let account = account_or_caller(agent, opts.account)?;
+            println!("{}", ledger::account_balance(agent, account).await?);
+        }
+        LedgerCommand::Transfer(opts) => {
+            let block = ledger::transfer(
+                agent,
+                opts.to.account_identifier(),
+                opts.amount,
+                opts.fee.unwrap_or(TRANSACTION_FEE),
+                opts.memo,
+                opts.from_subaccount,
+            )
+            .await?;
+            println!(
+                "Transferred {} to {} in block {block}.",
+                opts.amount, opts.to
+            );
+        }
+        LedgerCommand::Approve(opts) => {
+            let block = ledger::approve(
+                agent,
+                &opts.spender,
+                opts.amount,
+                opts.expires_at,
+                opts.from_subaccount,
+            )
+            .await?;
+            println!(
+                "Approved an allowance of {} for {} in block {block}.",
+                opts.amount, opts.spender
+            );
+        }
+        LedgerCommand::History(opts) => {
+            let account = account_or_caller(agent, opts.account)?;
+            let transactions =
+                ledger::account_transactions(agent, account, opts.start, opts.limit).await?;
+            print_transactions(&transactions);
+        }
+    }
+    Ok(())
+}
+
+/// The account identifier of an account, or of the caller's main account.
+fn account_or_caller(agent: &Agent, account: Option<Account>) -> anyhow::Result<AccountIdentifier> {
+    Ok(match account {
+        Some(account) => account.account_identifier(),
+        None => AccountIdentifier::new(agent.get_principal().map_err(|err| anyhow!(err))?, None),
+    })
+}
+
+/// Prints transactions as a table.
+fn print_transactions(transactions: &[TransactionWithId]) {
+    println!(
+        "{:<10}  {:<8}  {:>24}  {:<64}  TO",
+        "BLOCK", "TYPE", "AMOUNT", "FROM"
+    );
+    for TransactionWithId { id, transaction } in transactions {
+        let (kind, amount, from, to) = match &transaction.operation {
+            Operation::Approve {
+                from,
+                spender,
+                allowance,
+                ..
+            } => ("approve", allowance, from.as_str(), spender.as_str()),
+            Operation::Burn { from, amount } => ("burn", amount, from.as_str(), "-"),
+            Operation::Mint { to, amount } => ("mint", amount, "-", to.as_str()),
+            Operation::Transfer {
+                from, to, amount, ..
+            } => ("transfer", amount, from.as_str(), to.as_str()),
+        };
+        // `ICPTs` ignores the width, so the amount is padded as a string.
+        let amount = amount.to_string();
+        println!("{id:<10}  {kind:<8}  {amount:>24}  {from:<64}  {to}");
+    }
+}
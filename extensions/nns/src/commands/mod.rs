@@ -1,2 +1,18 @@
 pub(crate) mod import;
 pub(crate) mod install;
+pub(crate) mod ledger;
+pub(crate) mod neuron;
+pub(crate) mod propose;
+pub(crate) mod status;
+pub(crate) mod upgrade;
+
+use clap::ValueEnum;
+
+/// Formats that commands can print their results in.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    /// A table for humans.
+    Table,
+    /// JSON for scripts.
+    Json,
+}
@@ -0,0 +1,220 @@
+//! Code for the command line: `dfx nns neuron`
+use crate::connection::ConnectionOpts;
+use crate::governance::{self, NeuronInfo};
+use crate::ledger;
+use crate::neurons::{neuron_staking_subaccount, parse_topic};
+use crate::nns_types::account_identifier::AccountIdentifier;
+use crate::nns_types::icpts::{ICPTs, TRANSACTION_FEE};
+use dfx_extensions_utils::dependencies::download_wasms::nns::NNS_GOVERNANCE;
+
+use anyhow::anyhow;
+use clap::{Parser, Subcommand};
+use ic_agent::export::Principal;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Stakes and manages NNS neurons
+///
+/// The commands act as the dfx identity given with `--identity`, or the selected identity.  They work on any network
+/// with an NNS, including the local one installed by `dfx nns install`.
+#[derive(Parser)]
+#[clap(about)]
+pub struct NeuronOpts {
+    /// `dfx nns neuron` subcommand arguments.
+    #[clap(subcommand)]
+    subcmd: NeuronCommand,
+
+    /// The identity and network.
+    #[command(flatten)]
+    connection: ConnectionOpts,
+}
+
+/// Subcommands of `dfx nns neuron`.
+#[derive(Subcommand)]
+enum NeuronCommand {
+    /// Stake ICP from the main account of the identity in a new neuron.
+    Stake(StakeOpts),
+    /// List the neurons of the identity.
+    List,
+    /// Increase the dissolve delay of a neuron.
+    IncreaseDissolveDelay(IncreaseDissolveDelayOpts),
+    /// Start dissolving a neuron.
+    StartDissolving(NeuronIdOpts),
+    /// Stop dissolving a neuron.
+    StopDissolving(NeuronIdOpts),
+    /// Transfer the stake of a dissolved neuron to an account.
+    Disburse(DisburseOpts),
+    /// Set the neurons that a neuron follows on a topic.
+    Follow(FollowOpts),
+    /// Move part of the stake of a neuron to a new neuron.
+    Split(SplitOpts),
+}
+
+/// Arguments of `dfx nns neuron stake`.
+#[derive(Parser)]
+struct StakeOpts {
+    /// The amount of ICP to stake, e.g. 1.5.  The ledger fee is paid on top.
+    #[arg(long)]
+    amount: ICPTs,
+
+    /// The memo of the staking transfer.  Each neuron of an identity needs a different memo.  Defaults to the current time.
+    #[arg(long)]
+    memo: Option<u64>,
+}
+
+/// Arguments of commands that take only a neuron.
+#[derive(Parser)]
+struct NeuronIdOpts {
+    /// The ID of the neuron.
+    neuron_id: u64,
+}
+
+/// Arguments of `dfx nns neuron increase-dissolve-delay`.
+#[derive(Parser)]
+struct IncreaseDissolveDelayOpts {
+    /// The ID of the neuron.
+    neuron_id: u64,
+
+    /// The number of seconds to add to the dissolve delay.
+    #[arg(long, value_name = "SECONDS")]
+    by: u32,
+}
+
+/// Arguments of `dfx nns neuron disburse`.
+#[derive(Parser)]
+struct DisburseOpts {
+    /// The ID of the neuron.
+    neuron_id: u64,
+
+    /// The receiving account identifier.  Defaults to the main account of the identity.
+    #[arg(long, value_name = "ACCOUNT_ID")]
+    to: Option<AccountIdentifier>,
+
+    /// The amount of ICP to disburse.  Defaults to the whole stake.
+    #[arg(long)]
+    amount: Option<ICPTs>,
+}
+
+/// Arguments of `dfx nns neuron follow`.
+#[derive(Parser)]
+struct FollowOpts {
+    /// The ID of the neuron.
+    neuron_id: u64,
+
+    /// The topic, by name, e.g. governance, or by number.  `all` is the catch-all topic.
+    #[arg(long, value_parser = parse_topic)]
+    topic: i32,
+
+    /// A neuron to follow.  Without followees, the neuron stops following anyone on the topic.
+    #[arg(long, value_name = "NEURON_ID", action = clap::ArgAction::Append)]
+    followee: Vec<u64>,
+}
+
+/// Arguments of `dfx nns neuron split`.
+#[derive(Parser)]
+struct SplitOpts {
+    /// The ID of the neuron.
+    neuron_id: u64,
+
+    /// The amount of ICP to move to the new neuron.
+    #[arg(long)]
+    amount: ICPTs,
+}
+
+/// Executes `dfx nns neuron`.
+pub async fn exec(opts: NeuronOpts, dfx_cache_path: &Path) -> anyhow::Result<()> {
+    let dfx = opts.connection.dfx_interface(dfx_cache_path).await?;
+    let agent = dfx.agent();
+    match opts.subcmd {
+        NeuronCommand::Stake(opts) => {
+            let controller = agent.get_principal().map_err(|err| anyhow!(err))?;
+            let memo = match opts.memo {
+                Some(memo) => memo,
+                None => SystemTime::now()
+                    .duration_since(UNIX_EPOCH)?
+                    .as_nanos()
+                    .try_into()?,
+            };
+            let governance_id = Principal::from_text(NNS_GOVERNANCE.canister_id)?;
+            let staking_account = AccountIdentifier::new(
+                governance_id,
+                Some(neuron_staking_subaccount(&controller, memo)),
+            );
+            ledger::transfer(
+                agent,
+                staking_account,
+                opts.amount,
+                TRANSACTION_FEE,
+                memo,
+                None,
+            )
+            .await?;
+            let neuron_id = governance::claim_neuron(agent, controller, memo).await?;
+            println!("Staked {} in neuron {neuron_id}.", opts.amount);
+        }
+        NeuronCommand::List => print_neurons(&governance::list_neurons(agent).await?),
+        NeuronCommand::IncreaseDissolveDelay(opts) => {
+            governance::increase_dissolve_delay(agent, opts.neuron_id, opts.by).await?;
+            println!(
+                "Increased the dissolve delay of neuron {} by {} seconds.",
+                opts.neuron_id, opts.by
+            );
+        }
+        NeuronCommand::StartDissolving(opts) => {
+            governance::start_dissolving(agent, opts.neuron_id).await?;
+            println!("Neuron {} is dissolving.", opts.neuron_id);
+        }
+        NeuronCommand::StopDissolving(opts) => {
+            governance::stop_dissolving(agent, opts.neuron_id).await?;
+            println!("Neuron {} is not dissolving.", opts.neuron_id);
+        }
+        NeuronCommand::Disburse(opts) => {
+            let block =
+                governance::disburse_neuron(agent, opts.neuron_id, opts.to, opts.amount).await?;
+            println!("Disbursed neuron {} in block {block}.", opts.neuron_id);
+        }
+        NeuronCommand::Follow(opts) => {
+            governance::follow(agent, opts.neuron_id, opts.topic, &opts.followee).await?;
+            println!(
+                "Neuron {} follows [{}] on topic {}.",
+                opts.neuron_id,
+                opts.followee
+                    .iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                opts.topic
+            );
+        }
+        NeuronCommand::Split(opts) => {
+            let new_neuron_id =
+                governance::split_neuron(agent, opts.neuron_id, opts.amount).await?;
+            println!(
+                "Moved {} from neuron {} to the new neuron {new_neuron_id}.",
+                opts.amount, opts.neuron_id
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Prints neurons as a table.
+fn print_neurons(neurons: &[(u64, NeuronInfo)]) {
+    println!(
+        "{:<20}  {:<12}  {:>22}  {:>16}",
+        "NEURON", "STATE", "STAKE", "DISSOLVE DELAY"
+    );
+    for (id, info) in neurons {
+        let state = match info.state {
+            1 => "locked",
+            2 => "dissolving",
+            3 => "dissolved",
+            4 => "spawning",
+            _ => "unspecified",
+        };
+        // `ICPTs` ignores the width, so the columns are padded as strings.
+        let stake = ICPTs::from_e8s(info.stake_e8s).to_string();
+        let dissolve_delay = format!("{}s", info.dissolve_delay_seconds);
+        println!("{id:<20}  {state:<12}  {stake:>22}  {dissolve_delay:>16}");
+    }
+}
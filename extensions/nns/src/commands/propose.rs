@@ -0,0 +1,288 @@
+//! Code for the command line: `dfx nns propose`
+use crate::connection::ConnectionOpts;
+use crate::governance::{
+    self, Action, AddOrRemoveNodeProvider, InstallCode, Motion, NetworkEconomics, NodeProvider,
+    NodeProviderChange, ProposalOutcome, TEST_NEURON_1_ID,
+};
+use crate::install_config::icp_xdr_permyriad;
+use crate::install_nns::get_and_check_provider;
+use crate::ledger::Account;
+use crate::nns_types::icpts::ICPTs;
+
+use anyhow::{bail, Context};
+use clap::{Parser, Subcommand, ValueEnum};
+use ic_agent::export::Principal;
+use rust_decimal::Decimal;
+use std::path::{Path, PathBuf};
+
+/// Submits an NNS proposal
+///
+/// On a local network the proposal is made by the test neuron, whose vote adopts it at once, and the command waits
+/// until it has been executed.  Elsewhere, pass `--neuron-id` to propose with a neuron of the identity.
+#[derive(Parser)]
+#[clap(about)]
+pub struct ProposeOpts {
+    /// The kind of proposal.
+    #[clap(subcommand)]
+    kind: ProposalKind,
+
+    /// Propose with this neuron of the identity instead of the test neuron, and do not wait for the decision.
+    #[arg(long, global = true)]
+    neuron_id: Option<u64>,
+
+    /// The title of the proposal.  Defaults to a title that describes the action.
+    #[arg(long, global = true)]
+    title: Option<String>,
+
+    /// The summary of the proposal, in markdown.  Defaults to the title.
+    #[arg(long, global = true)]
+    summary: Option<String>,
+
+    /// A URL with more information about the proposal.
+    #[arg(long, global = true, default_value = "")]
+    url: String,
+
+    /// The identity and network.
+    #[command(flatten)]
+    connection: ConnectionOpts,
+}
+
+/// Kinds of proposals that `dfx nns propose` can make.
+#[derive(Subcommand)]
+enum ProposalKind {
+    /// Propose a motion.
+    Motion(MotionOpts),
+    /// Propose to install code in a canister controlled by NNS root.
+    InstallCode(InstallCodeOpts),
+    /// Propose to update the network economics.  Parameters that are not given keep their current values.
+    NetworkEconomics(NetworkEconomicsOpts),
+    /// Propose to add a node provider.
+    AddNodeProvider(AddNodeProviderOpts),
+    /// Propose to remove a node provider.
+    RemoveNodeProvider(RemoveNodeProviderOpts),
+}
+
+/// Arguments of `dfx nns propose motion`.
+#[derive(Parser)]
+struct MotionOpts {
+    /// The text of the motion.
+    #[arg(long)]
+    text: String,
+}
+
+/// How `dfx nns propose install-code` installs the wasm.
+#[derive(Clone, Copy, ValueEnum)]
+enum InstallMode {
+    /// Install the wasm in an empty canister.
+    Install,
+    /// Replace the wasm and the state of the canister.
+    Reinstall,
+    /// Upgrade the canister, keeping its stable memory.
+    Upgrade,
+}
+
+impl InstallMode {
+    /// The number of the mode in the governance canister's `InstallCode` proposal.
+    fn id(self) -> i32 {
+        match self {
+            InstallMode::Install => 1,
+            InstallMode::Reinstall => 2,
+            InstallMode::Upgrade => 3,
+        }
+    }
+}
+
+/// Arguments of `dfx nns propose install-code`.
+#[derive(Parser)]
+struct InstallCodeOpts {
+    /// The canister to install the code in.
+    canister_id: Principal,
+
+    /// The wasm module, optionally gzipped.
+    #[arg(long)]
+    wasm: PathBuf,
+
+    /// The install argument in candid text format.
+    #[arg(long, default_value = "()")]
+    arg: String,
+
+    /// How to install the wasm.
+    #[arg(long, value_enum, default_value_t = InstallMode::Upgrade)]
+    mode: InstallMode,
+
+    /// Install without stopping the canister first.
+    #[arg(long)]
+    skip_stopping: bool,
+}
+
+/// Arguments of `dfx nns propose network-economics`.
+#[derive(Parser)]
+struct NetworkEconomicsOpts {
+    /// The fee, in ICP, that the proposer of a rejected proposal pays.
+    #[arg(long, value_name = "ICP")]
+    reject_cost: Option<ICPTs>,
+
+    /// The minimum stake of a neuron, in ICP.
+    #[arg(long, value_name = "ICP")]
+    neuron_minimum_stake: Option<ICPTs>,
+
+    /// The fee, in ICP, of a neuron management proposal.
+    #[arg(long, value_name = "ICP")]
+    neuron_management_fee_per_proposal: Option<ICPTs>,
+
+    /// The minimum ICP/XDR conversion rate, in XDR per ICP.
+    #[arg(long, value_name = "RATE")]
+    minimum_icp_xdr_rate: Option<Decimal>,
+
+    /// The dissolve delay, in seconds, of spawned neurons.
+    #[arg(long, value_name = "SECONDS")]
+    neuron_spawn_dissolve_delay: Option<u64>,
+
+    /// The maximum monthly reward of a node provider, in ICP.
+    #[arg(long, value_name = "ICP")]
+    maximum_node_provider_rewards: Option<ICPTs>,
+
+    /// The ledger transaction fee, in ICP.
+    #[arg(long, value_name = "ICP")]
+    transaction_fee: Option<ICPTs>,
+
+    /// How many decided proposals are kept per topic.
+    #[arg(long, value_name = "COUNT")]
+    max_proposals_to_keep_per_topic: Option<u32>,
+}
+
+/// Arguments of `dfx nns propose add-node-provider`.
+#[derive(Parser)]
+struct AddNodeProviderOpts {
+    /// The principal of the node provider.
+    node_provider: Principal,
+
+    /// The account that receives the rewards.  Defaults to the main account of the node provider.
+    #[arg(long, value_name = "ACCOUNT")]
+    reward_account: Option<Account>,
+}
+
+/// Arguments of `dfx nns propose remove-node-provider`.
+#[derive(Parser)]
+struct RemoveNodeProviderOpts {
+    /// The principal of the node provider.
+    node_provider: Principal,
+}
+
+/// Executes `dfx nns propose`.
+pub async fn exec(opts: ProposeOpts, dfx_cache_path: &Path) -> anyhow::Result<()> {
+    let (default_title, action) = proposal_action(opts.kind)?;
+    let title = opts.title.unwrap_or(default_title);
+    let summary = opts.summary.unwrap_or_else(|| title.clone());
+
+    let dfx = opts.connection.dfx_interface(dfx_cache_path).await?;
+    let network = dfx.network_descriptor();
+    if let Some(neuron_id) = opts.neuron_id {
+        let proposal_id =
+            governance::make_proposal(dfx.agent(), neuron_id, &title, &summary, &opts.url, action)
+                .await?;
+        println!("Submitted proposal {proposal_id}.");
+        return Ok(());
+    }
+    if network.local_server_descriptor.is_none() {
+        bail!(
+            "Pass --neuron-id to propose on network '{}'.  Only local networks have the test neuron.",
+            network.name
+        );
+    }
+    let agent = governance::test_neuron_agent(&get_and_check_provider(network)?).await?;
+    let proposal_id = governance::make_proposal(
+        &agent,
+        TEST_NEURON_1_ID,
+        &title,
+        &summary,
+        &opts.url,
+        action,
+    )
+    .await?;
+    println!("Submitted proposal {proposal_id}.");
+    match governance::wait_for_proposal(&agent, proposal_id).await? {
+        ProposalOutcome::Executed => {
+            println!("Proposal {proposal_id} was executed.");
+            Ok(())
+        }
+        outcome => bail!("Proposal {proposal_id} was {outcome}"),
+    }
+}
+
+/// The default title and the action of a proposal.
+fn proposal_action(kind: ProposalKind) -> anyhow::Result<(String, Action)> {
+    Ok(match kind {
+        ProposalKind::Motion(opts) => (
+            "Motion".to_string(),
+            Action::Motion(Motion {
+                motion_text: opts.text,
+            }),
+        ),
+        ProposalKind::InstallCode(opts) => {
+            let wasm_module = std::fs::read(&opts.wasm)
+                .with_context(|| format!("Failed to read {}.", opts.wasm.display()))?;
+            let arg = candid_parser::parse_idl_args(&opts.arg)
+                .with_context(|| format!("Invalid candid argument '{}'.", opts.arg))?
+                .to_bytes()?;
+            (
+                format!("Install code in {}", opts.canister_id),
+                Action::InstallCode(InstallCode {
+                    canister_id: Some(opts.canister_id),
+                    wasm_module: Some(wasm_module),
+                    arg: Some(arg),
+                    install_mode: Some(opts.mode.id()),
+                    skip_stopping_before_installing: Some(opts.skip_stopping),
+                }),
+            )
+        }
+        ProposalKind::NetworkEconomics(opts) => {
+            let e8s = |amount: Option<ICPTs>| amount.map(ICPTs::get_e8s).unwrap_or_default();
+            let economics = NetworkEconomics {
+                reject_cost_e8s: e8s(opts.reject_cost),
+                neuron_minimum_stake_e8s: e8s(opts.neuron_minimum_stake),
+                neuron_management_fee_per_proposal_e8s: e8s(opts.neuron_management_fee_per_proposal),
+                minimum_icp_xdr_rate: opts
+                    .minimum_icp_xdr_rate
+                    .map(icp_xdr_permyriad)
+                    .transpose()?
+                    .unwrap_or_default(),
+                neuron_spawn_dissolve_delay_seconds: opts
+                    .neuron_spawn_dissolve_delay
+                    .unwrap_or_default(),
+                maximum_node_provider_rewards_e8s: e8s(opts.maximum_node_provider_rewards),
+                transaction_fee_e8s: e8s(opts.transaction_fee),
+                max_proposals_to_keep_per_topic: opts
+                    .max_proposals_to_keep_per_topic
+                    .unwrap_or_default(),
+            };
+            if economics == NetworkEconomics::default() {
+                bail!("Pass at least one network economics parameter to change.");
+            }
+            (
+                "Update the network economics".to_string(),
+                Action::ManageNetworkEconomics(economics),
+            )
+        }
+        ProposalKind::AddNodeProvider(opts) => (
+            format!("Add node provider {}", opts.node_provider),
+            Action::AddOrRemoveNodeProvider(AddOrRemoveNodeProvider {
+                change: Some(NodeProviderChange::ToAdd(NodeProvider {
+                    id: Some(opts.node_provider),
+                    reward_account: opts
+                        .reward_account
+                        .map(|account| account.account_identifier().into()),
+                })),
+            }),
+        ),
+        ProposalKind::RemoveNodeProvider(opts) => (
+            format!("Remove node provider {}", opts.node_provider),
+            Action::AddOrRemoveNodeProvider(AddOrRemoveNodeProvider {
+                change: Some(NodeProviderChange::ToRemove(NodeProvider {
+                    id: Some(opts.node_provider),
+                    reward_account: None,
+                })),
+            }),
+        ),
+    })
+}
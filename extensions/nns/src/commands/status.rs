@@ -0,0 +1,82 @@
+//! Code for the command line: `dfx nns status`
+use crate::commands::OutputFormat;
+use crate::install_state::InstallState;
+use crate::nns_status::{nns_status, CanisterStatus};
+use clap::Parser;
+use dfx_core::DfxInterfaceBuilder;
+use std::path::Path;
+
+/// Shows which NNS canisters are installed on the local dfx server
+///
+/// Each canister is marked as:
+/// - missing: there is no canister with this ID.
+/// - empty: the canister exists but no wasm is installed.
+/// - matching: the canister runs the wasm that `dfx nns install` installed.
+/// - drifted: the canister runs a different wasm.
+/// - unverified: there is no wasm to compare with.
+///
+/// The canisters and wasms recorded by `dfx nns install` are expected.  Without a record, the default canisters are
+/// expected to run the wasms in the cache.
+#[derive(Parser)]
+#[clap(about)]
+pub struct StatusOpts {
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
+}
+
+/// Executes `dfx nns status`.
+pub async fn exec(opts: StatusOpts, dfx_cache_path: &Path) -> anyhow::Result<()> {
+    let dfx = DfxInterfaceBuilder::new()
+        .anonymous()
+        .with_extension_manager_from_cache_path(dfx_cache_path)?
+        .build()
+        .await?;
+
+    let install_state = InstallState::load(dfx.network_descriptor())?;
+    let statuses = nns_status(dfx.agent(), dfx_cache_path, install_state.as_ref()).await?;
+    match opts.output {
+        OutputFormat::Table => {
+            if let Some(install_state) = &install_state {
+                println!("Installed from IC commit {}.\n", install_state.ic_commit);
+            }
+            print_table(&statuses)
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&statuses)?),
+    }
+    Ok(())
+}
+
+/// Prints the canister statuses as a table.
+fn print_table(statuses: &[CanisterStatus]) {
+    println!(
+        "{:<20}  {:<27}  {:<10}  {:<16}  {:>18}  CONTROLLERS",
+        "CANISTER", "ID", "STATUS", "MODULE HASH", "CYCLES"
+    );
+    for status in statuses {
+        let module_hash = status
+            .module_hash
+            .as_deref()
+            .map(|hash| format!("0x{}", &hash[..hash.len().min(14)]))
+            .unwrap_or_else(|| "-".to_string());
+        let cycles = status
+            .cycles
+            .map(|cycles| cycles.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let controllers = status
+            .controllers
+            .iter()
+            .map(|controller| controller.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!(
+            "{:<20}  {:<27}  {:<10}  {:<16}  {:>18}  {}",
+            status.canister_name,
+            status.canister_id.to_string(),
+            status.status,
+            module_hash,
+            cycles,
+            controllers
+        );
+    }
+}
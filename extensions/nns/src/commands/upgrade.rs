@@ -0,0 +1,90 @@
+//! Code for the command line: `dfx nns upgrade`
+use crate::install_nns::get_and_check_provider;
+use crate::install_state::InstallState;
+use crate::upgrade_nns::{find_core_canister, upgrade_nns_canister, WasmSource};
+
+use anyhow::{bail, Context};
+use clap::Parser;
+use dfx_core::DfxInterfaceBuilder;
+use slog::Logger;
+use std::path::{Path, PathBuf};
+
+/// Upgrades an NNS canister on the local dfx server
+///
+/// The upgrade goes through NNS root, as on mainnet: the test neuron proposes to install the new wasm, the proposal
+/// is adopted at once, and the command waits until the canister runs the new wasm.
+#[derive(Parser)]
+#[clap(about)]
+pub struct UpgradeOpts {
+    /// The NNS canister to upgrade, e.g. `nns-governance` or `governance`.
+    canister: String,
+
+    /// Upgrade to the wasm built at this commit of the IC repository.
+    #[arg(long, conflicts_with = "wasm", required_unless_present = "wasm")]
+    ic_commit: Option<String>,
+
+    /// Upgrade to this wasm file, optionally gzipped.
+    #[arg(long)]
+    wasm: Option<PathBuf>,
+
+    /// The upgrade argument in candid text format.
+    #[arg(long, default_value = "()")]
+    arg: String,
+}
+
+/// Executes `dfx nns upgrade`.
+pub async fn exec(opts: UpgradeOpts, dfx_cache_path: &Path, logger: &Logger) -> anyhow::Result<()> {
+    let canister = find_core_canister(&opts.canister)?;
+    let wasm_source = match (opts.ic_commit, opts.wasm) {
+        (_, Some(path)) => WasmSource::File(path),
+        (Some(ic_commit), None) => WasmSource::IcCommit(ic_commit),
+        (None, None) => bail!("Pass --ic-commit or --wasm."),
+    };
+    let arg = candid_parser::parse_idl_args(&opts.arg)
+        .with_context(|| format!("Invalid candid argument '{}'.", opts.arg))?
+        .to_bytes()?;
+
+    let dfx = DfxInterfaceBuilder::new()
+        .anonymous()
+        .with_extension_manager_from_cache_path(dfx_cache_path)?
+        .build()
+        .await?;
+    let network_descriptor = dfx.network_descriptor();
+    if network_descriptor.local_server_descriptor.is_none() {
+        bail!(
+            "dfx nns upgrade only works on local networks, not on '{}'.  Use dfx nns propose install-code elsewhere.",
+            network_descriptor.name
+        );
+    }
+    let provider_url = get_and_check_provider(network_descriptor)?;
+
+    // Upgrade to the same kind of wasm that `dfx nns install` installed, which is the test wasm if there is one.
+    let mut install_state = InstallState::load(network_descriptor)?;
+    let wasm_name = install_state
+        .as_ref()
+        .and_then(|install_state| install_state.canister(canister.canister_name))
+        .map(|installed| installed.wasm_name.clone())
+        .unwrap_or_else(|| {
+            canister
+                .test_wasm_name
+                .unwrap_or(canister.wasm_name)
+                .to_string()
+        });
+    let outcome = upgrade_nns_canister(
+        logger,
+        dfx.agent(),
+        &provider_url,
+        dfx_cache_path,
+        canister,
+        &wasm_name,
+        &wasm_source,
+        arg,
+    )
+    .await?;
+    if let Some(install_state) = &mut install_state {
+        install_state.record_wasm(canister.canister_name, outcome.wasm_hash());
+        install_state.save(network_descriptor)?;
+    }
+    println!("{outcome}");
+    Ok(())
+}
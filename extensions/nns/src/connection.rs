@@ -0,0 +1,55 @@
+//! How `dfx nns` commands that act as a dfx identity connect to a network.
+#![warn(missing_docs)]
+#![warn(clippy::missing_docs_in_private_items)]
+
+use anyhow::Context;
+use clap::Args;
+use dfx_core::interface::builder::IdentityPicker;
+use dfx_core::DfxInterface;
+use std::path::Path;
+
+/// The `--identity` and `--network` flags.
+#[derive(Args)]
+pub struct ConnectionOpts {
+    /// The dfx identity to run this command as.  Defaults to the identity selected with `dfx identity use`.
+    #[arg(long, global = true)]
+    identity: Option<String>,
+
+    /// The network to connect to: a network name like `local` or `ic`, or a URL.  Defaults to the local network.
+    #[arg(long, global = true, env = "DFX_NETWORK")]
+    network: Option<String>,
+}
+
+impl ConnectionOpts {
+    /// A dfx interface for the chosen identity and network.
+    ///
+    /// # Errors
+    /// - Returns an error if the identity cannot be loaded or the network is unknown or unreachable.
+    pub async fn dfx_interface(&self, dfx_cache_path: &Path) -> anyhow::Result<DfxInterface> {
+        let network = self.network.as_deref().unwrap_or("local");
+        let identity = self
+            .identity
+            .clone()
+            .map(IdentityPicker::Named)
+            .unwrap_or(IdentityPicker::Selected);
+        let dfx = DfxInterface::builder()
+            .with_identity(identity)
+            .with_network_named(network)
+            .with_extension_manager_from_cache_path(dfx_cache_path)?
+            .build()
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to connect to network '{network}' as identity '{}'.",
+                    self.identity.as_deref().unwrap_or("(selected)")
+                )
+            })?;
+        if !dfx.network_descriptor().is_ic {
+            dfx.agent()
+                .fetch_root_key()
+                .await
+                .with_context(|| format!("Failed to fetch the root key of network '{network}'."))?;
+        }
+        Ok(dfx)
+    }
+}
@@ -0,0 +1,724 @@
+//! Talks to the NNS governance canister: makes proposals with the test neuron and manages neurons.
+//!
+//! The local NNS governance canister is initialized with test neurons whose voting power is a majority,
+//! so their proposals are adopted and executed as soon as they are made.
+#![warn(missing_docs)]
+#![warn(clippy::missing_docs_in_private_items)]
+
+use crate::nns_types::account_identifier::AccountIdentifier;
+use crate::nns_types::icpts::ICPTs;
+use dfx_extensions_utils::dependencies::download_wasms::nns::NNS_GOVERNANCE;
+
+use anyhow::{anyhow, bail, Context};
+use backoff::backoff::Backoff;
+use backoff::ExponentialBackoff;
+use candid::{CandidType, Decode, Encode};
+use fn_error_context::context;
+use ic_agent::export::Principal;
+use ic_agent::identity::BasicIdentity;
+use ic_agent::Agent;
+pub(crate) use ic_nervous_system_common_test_keys::TEST_NEURON_1_ID;
+use ic_nervous_system_common_test_keys::TEST_NEURON_1_OWNER_KEYPAIR;
+use reqwest::Url;
+use serde::Deserialize;
+use std::fmt;
+use std::time::Duration;
+
+/// NNS functions that `dfx nns` proposes to execute.
+#[derive(Clone, Copy, Debug)]
+pub enum NnsFunction {
+    /// Sets the ICP/XDR conversion rate of the cycles minting canister.
+    IcpXdrConversionRate,
+    /// Sets the subnets the cycles minting canister may create canisters in.
+    SetAuthorizedSubnetworks,
+}
+
+impl NnsFunction {
+    /// The number of the function in the governance canister's `NnsFunction` enum.
+    fn id(self) -> i32 {
+        match self {
+            NnsFunction::IcpXdrConversionRate => 10,
+            NnsFunction::SetAuthorizedSubnetworks => 14,
+        }
+    }
+}
+
+/// Argument of the cycles minting canister's `set_authorized_subnetwork_list` method.
+#[derive(CandidType)]
+pub struct SetAuthorizedSubnetworkListArgs {
+    /// The principal the subnets are authorized for, or `None` for the default subnets.
+    pub who: Option<Principal>,
+    /// The authorized subnets.
+    pub subnets: Vec<Principal>,
+}
+
+/// Argument of the cycles minting canister's `set_icp_xdr_conversion_rate` method.
+#[derive(CandidType)]
+pub struct UpdateIcpXdrConversionRatePayload {
+    /// Where the rate comes from.
+    pub data_source: String,
+    /// When the rate was observed.  It must be later than the time of the current rate.
+    pub timestamp_seconds: u64,
+    /// The number of ten-thousandths of an XDR that one ICP is worth.
+    pub xdr_permyriad_per_icp: u64,
+    /// Why the rate is set by proposal.
+    pub reason: Option<UpdateIcpXdrConversionRatePayloadReason>,
+}
+
+/// Why the ICP/XDR conversion rate is set by proposal.  Only the reasons used by `dfx nns` are listed.
+#[derive(CandidType)]
+pub enum UpdateIcpXdrConversionRatePayloadReason {
+    /// The rate that the cycles minting canister has is out of date.
+    OldRate,
+}
+
+/// The ID of a neuron.
+#[derive(CandidType, Deserialize)]
+struct NeuronId {
+    /// The neuron number.
+    id: u64,
+}
+
+/// The ID of a proposal.
+#[derive(CandidType, Deserialize)]
+struct ProposalId {
+    /// The proposal number.
+    id: u64,
+}
+
+/// A proposal to call an NNS canister method that is reserved for governance.
+#[derive(CandidType)]
+pub struct ExecuteNnsFunction {
+    /// The function to execute.
+    nns_function: i32,
+    /// The candid encoded argument of the function.
+    payload: Vec<u8>,
+}
+
+/// A proposal without effect other than recording whether it was adopted.
+#[derive(CandidType)]
+pub struct Motion {
+    /// The text of the motion.
+    pub motion_text: String,
+}
+
+/// A proposal to install code in a canister controlled by NNS root.
+#[derive(CandidType)]
+pub struct InstallCode {
+    /// The canister.
+    pub canister_id: Option<Principal>,
+    /// The wasm module.
+    pub wasm_module: Option<Vec<u8>>,
+    /// The candid encoded install argument.
+    pub arg: Option<Vec<u8>>,
+    /// 1 to install, 2 to reinstall and 3 to upgrade.
+    pub install_mode: Option<i32>,
+    /// Whether to install without stopping the canister first.
+    pub skip_stopping_before_installing: Option<bool>,
+}
+
+/// New network economics.  Fields that are zero keep their current values.
+#[derive(CandidType, Default, PartialEq, Eq)]
+pub struct NetworkEconomics {
+    /// The fee, in e8s, that the proposer of a rejected proposal pays.
+    pub reject_cost_e8s: u64,
+    /// The minimum stake of a neuron, in e8s.
+    pub neuron_minimum_stake_e8s: u64,
+    /// The fee, in e8s, of a neuron management proposal.
+    pub neuron_management_fee_per_proposal_e8s: u64,
+    /// The minimum ICP/XDR conversion rate, in ten-thousandths of an XDR per ICP.
+    pub minimum_icp_xdr_rate: u64,
+    /// The dissolve delay, in seconds, of spawned neurons.
+    pub neuron_spawn_dissolve_delay_seconds: u64,
+    /// The maximum monthly reward of a node provider, in e8s.
+    pub maximum_node_provider_rewards_e8s: u64,
+    /// The ledger transaction fee, in e8s.
+    pub transaction_fee_e8s: u64,
+    /// How many decided proposals are kept per topic.
+    pub max_proposals_to_keep_per_topic: u32,
+}
+
+/// A node provider.
+#[derive(CandidType)]
+pub struct NodeProvider {
+    /// The principal of the node provider.
+    pub id: Option<Principal>,
+    /// The account that receives the rewards.  Defaults to the main account of the node provider.
+    pub reward_account: Option<GovernanceAccountIdentifier>,
+}
+
+/// Whether to add or remove a node provider.
+#[derive(CandidType)]
+pub enum NodeProviderChange {
+    /// Add the node provider.
+    ToAdd(NodeProvider),
+    /// Remove the node provider.
+    ToRemove(NodeProvider),
+}
+
+/// A proposal to add or remove a node provider.
+#[derive(CandidType)]
+pub struct AddOrRemoveNodeProvider {
+    /// The change.
+    pub change: Option<NodeProviderChange>,
+}
+
+/// The action of a proposal.  Only the actions used by `dfx nns` are listed.
+#[derive(CandidType)]
+pub enum Action {
+    /// Execute an NNS function.
+    ExecuteNnsFunction(ExecuteNnsFunction),
+    /// A motion.
+    Motion(Motion),
+    /// Install code in a canister controlled by NNS root.
+    InstallCode(InstallCode),
+    /// Update the network economics.
+    ManageNetworkEconomics(NetworkEconomics),
+    /// Add or remove a node provider.
+    AddOrRemoveNodeProvider(AddOrRemoveNodeProvider),
+}
+
+/// A proposal.
+#[derive(CandidType)]
+struct Proposal {
+    /// The title of the proposal.
+    title: Option<String>,
+    /// The summary of the proposal.
+    summary: String,
+    /// A URL with more information.
+    url: String,
+    /// What the proposal does when it is adopted.
+    action: Option<Action>,
+}
+
+/// Identifies the neuron to claim or refresh by the memo of the staking transfer and its controller.
+#[derive(CandidType)]
+struct MemoAndController {
+    /// The memo the stake was transferred with.
+    memo: u64,
+    /// The controller of the neuron.  Defaults to the caller.
+    controller: Option<Principal>,
+}
+
+/// Which neuron to claim or refresh.  Only the ways used by `dfx nns` are listed.
+#[derive(CandidType)]
+enum By {
+    /// By the memo of the staking transfer and the controller.
+    MemoAndController(MemoAndController),
+}
+
+/// Claims a neuron whose stake has been transferred to its staking subaccount, or refreshes its stake.
+#[derive(CandidType)]
+struct ClaimOrRefresh {
+    /// Which neuron.
+    by: Option<By>,
+}
+
+/// Increases the dissolve delay of a neuron.
+#[derive(CandidType)]
+struct IncreaseDissolveDelay {
+    /// The number of seconds to add.
+    additional_dissolve_delay_seconds: u32,
+}
+
+/// Starts dissolving a neuron.
+#[derive(CandidType)]
+struct StartDissolving {}
+
+/// Stops dissolving a neuron.
+#[derive(CandidType)]
+struct StopDissolving {}
+
+/// A change to a neuron's configuration.  Only the operations used by `dfx nns` are listed.
+#[derive(CandidType)]
+enum Operation {
+    /// Increase the dissolve delay.
+    IncreaseDissolveDelay(IncreaseDissolveDelay),
+    /// Start dissolving.
+    StartDissolving(StartDissolving),
+    /// Stop dissolving.
+    StopDissolving(StopDissolving),
+}
+
+/// Changes the configuration of a neuron.
+#[derive(CandidType)]
+struct Configure {
+    /// The change.
+    operation: Option<Operation>,
+}
+
+/// An account of the ICP ledger as the governance canister expects it.
+#[derive(CandidType)]
+pub struct GovernanceAccountIdentifier {
+    /// The account identifier, with its checksum.
+    hash: Vec<u8>,
+}
+
+impl From<AccountIdentifier> for GovernanceAccountIdentifier {
+    fn from(account: AccountIdentifier) -> Self {
+        GovernanceAccountIdentifier {
+            hash: account.to_vec(),
+        }
+    }
+}
+
+/// Transfers the stake of a dissolved neuron to an account.
+#[derive(CandidType)]
+struct Disburse {
+    /// The receiving account.  Defaults to the caller's main account.
+    to_account: Option<GovernanceAccountIdentifier>,
+    /// The amount to disburse.  Defaults to the whole stake.
+    amount: Option<ICPTs>,
+}
+
+/// Sets the neurons a neuron follows on a topic.
+#[derive(CandidType)]
+struct Follow {
+    /// The topic.
+    topic: i32,
+    /// The followed neurons.  An empty list removes the followees of the topic.
+    followees: Vec<NeuronId>,
+}
+
+/// Splits a neuron into two.
+#[derive(CandidType)]
+struct Split {
+    /// The stake, in e8s, of the new neuron.
+    amount_e8s: u64,
+}
+
+/// A neuron command.  Only the commands used by `dfx nns` are listed.
+#[derive(CandidType)]
+enum Command {
+    /// Make a proposal.
+    MakeProposal(Proposal),
+    /// Claim or refresh a neuron.
+    ClaimOrRefresh(ClaimOrRefresh),
+    /// Change the configuration of a neuron.
+    Configure(Configure),
+    /// Disburse the stake of a neuron.
+    Disburse(Disburse),
+    /// Set followees.
+    Follow(Follow),
+    /// Split a neuron.
+    Split(Split),
+}
+
+/// Argument of the governance canister's `manage_neuron` method.
+#[derive(CandidType)]
+struct ManageNeuron {
+    /// The neuron that executes the command.
+    id: Option<NeuronId>,
+    /// The command.
+    command: Option<Command>,
+}
+
+/// An error returned by the governance canister.
+#[derive(Debug, CandidType, Deserialize)]
+struct GovernanceError {
+    /// The kind of error.
+    error_type: i32,
+    /// A description of the error.
+    error_message: String,
+}
+
+/// The result of making a proposal.
+#[derive(CandidType, Deserialize)]
+struct MakeProposalResponse {
+    /// The ID of the new proposal.
+    proposal_id: Option<ProposalId>,
+}
+
+/// The result of claiming or refreshing a neuron.
+#[derive(CandidType, Deserialize)]
+struct ClaimOrRefreshResponse {
+    /// The ID of the neuron.
+    refreshed_neuron_id: Option<NeuronId>,
+}
+
+/// The result of disbursing a neuron.
+#[derive(CandidType, Deserialize)]
+struct DisburseResponse {
+    /// The ledger block of the transfer.
+    transfer_block_height: u64,
+}
+
+/// The result of splitting a neuron.
+#[derive(CandidType, Deserialize)]
+struct SplitResponse {
+    /// The ID of the new neuron.
+    created_neuron_id: Option<NeuronId>,
+}
+
+/// The result of a command that returns nothing.
+#[derive(CandidType, Deserialize)]
+struct EmptyResponse {}
+
+/// The result of a neuron command.  Only the results of the commands used by `dfx nns` are listed.
+#[derive(CandidType, Deserialize)]
+enum CommandResponse {
+    /// The command failed.
+    Error(GovernanceError),
+    /// A proposal was made.
+    MakeProposal(MakeProposalResponse),
+    /// A neuron was claimed or refreshed.
+    ClaimOrRefresh(ClaimOrRefreshResponse),
+    /// The configuration of a neuron was changed.
+    Configure(EmptyResponse),
+    /// The stake of a neuron was disbursed.
+    Disburse(DisburseResponse),
+    /// The followees of a neuron were set.
+    Follow(EmptyResponse),
+    /// A neuron was split.
+    Split(SplitResponse),
+}
+
+/// Response of the governance canister's `manage_neuron` method.
+#[derive(CandidType, Deserialize)]
+struct ManageNeuronResponse {
+    /// The result of the command.
+    command: Option<CommandResponse>,
+}
+
+/// Argument of the governance canister's `list_neurons` method.
+#[derive(CandidType)]
+struct ListNeurons {
+    /// Neurons to list in addition to the caller's.
+    neuron_ids: Vec<u64>,
+    /// Whether to list the neurons the caller controls or is a hot key of.
+    include_neurons_readable_by_caller: bool,
+}
+
+/// The part of the governance canister's `NeuronInfo` that `dfx nns` shows.
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct NeuronInfo {
+    /// 1 if the neuron is not dissolving, 2 if it is dissolving, 3 if it is dissolved and 4 if it is spawning.
+    pub state: i32,
+    /// The dissolve delay, or the time left until the neuron is dissolved, in seconds.
+    pub dissolve_delay_seconds: u64,
+    /// The stake in e8s, minus fees.
+    pub stake_e8s: u64,
+    /// How long the neuron has not been dissolving, in seconds.
+    pub age_seconds: u64,
+    /// The voting power of the neuron.
+    pub voting_power: u64,
+}
+
+/// The part of the governance canister's `ListNeuronsResponse` that is used here.
+#[derive(CandidType, Deserialize)]
+struct ListNeuronsResponse {
+    /// The listed neurons, by ID.
+    neuron_infos: Vec<(u64, NeuronInfo)>,
+}
+
+/// The part of the governance canister's `ProposalInfo` that is used here.
+#[derive(CandidType, Deserialize)]
+struct ProposalInfo {
+    /// 1 if the proposal is open, 2 if it was rejected, 3 if it was adopted, 4 if it was executed and 5 if the
+    /// execution failed.
+    status: i32,
+    /// When the proposal was executed, or zero.
+    executed_timestamp_seconds: u64,
+    /// When the execution failed, or zero.
+    failed_timestamp_seconds: u64,
+    /// Why the execution failed.
+    failure_reason: Option<GovernanceError>,
+}
+
+/// What became of a decided proposal.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ProposalOutcome {
+    /// The proposal was adopted and executed.
+    Executed,
+    /// The proposal was rejected.
+    Rejected,
+    /// The proposal was adopted but its execution failed.
+    Failed(String),
+}
+
+impl fmt::Display for ProposalOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProposalOutcome::Executed => write!(f, "executed"),
+            ProposalOutcome::Rejected => write!(f, "rejected"),
+            ProposalOutcome::Failed(reason) => write!(f, "failed: {reason}"),
+        }
+    }
+}
+
+/// An agent that signs with the key of the test neuron's controller.
+#[context("Failed to create an agent for the test neuron.")]
+pub async fn test_neuron_agent(provider_url: &Url) -> anyhow::Result<Agent> {
+    let identity = BasicIdentity::from_raw_key(&TEST_NEURON_1_OWNER_KEYPAIR.secret_key);
+    let agent = Agent::builder()
+        .with_url(provider_url.as_str())
+        .with_identity(identity)
+        .build()?;
+    agent.fetch_root_key().await?;
+    Ok(agent)
+}
+
+/// Proposes, as the test neuron, to execute an NNS function, and waits until the proposal has been executed.
+///
+/// # Errors
+/// - Returns an error if the proposal cannot be made, or if it is not executed successfully.
+#[context("Failed to execute the NNS function {:?} by proposal.", nns_function)]
+pub async fn execute_nns_function(
+    agent: &Agent,
+    title: &str,
+    summary: &str,
+    nns_function: NnsFunction,
+    payload: Vec<u8>,
+) -> anyhow::Result<u64> {
+    let action = Action::ExecuteNnsFunction(ExecuteNnsFunction {
+        nns_function: nns_function.id(),
+        payload,
+    });
+    let proposal_id = make_proposal(agent, TEST_NEURON_1_ID, title, summary, "", action).await?;
+    match wait_for_proposal(agent, proposal_id).await? {
+        ProposalOutcome::Executed => Ok(proposal_id),
+        outcome => bail!("Proposal {proposal_id} was {outcome}"),
+    }
+}
+
+/// Makes a proposal with a neuron of the caller.
+///
+/// Returns the ID of the proposal.
+#[context("Failed to make a proposal with neuron {}.", neuron_id)]
+pub async fn make_proposal(
+    agent: &Agent,
+    neuron_id: u64,
+    title: &str,
+    summary: &str,
+    url: &str,
+    action: Action,
+) -> anyhow::Result<u64> {
+    let proposal = Proposal {
+        title: Some(title.to_string()),
+        summary: summary.to_string(),
+        url: url.to_string(),
+        action: Some(action),
+    };
+    match manage_neuron(agent, Some(neuron_id), Command::MakeProposal(proposal))
+        .await
+        .with_context(|| "The governance canister rejected the proposal")?
+    {
+        CommandResponse::MakeProposal(MakeProposalResponse {
+            proposal_id: Some(ProposalId { id }),
+        }) => Ok(id),
+        _ => bail!("The governance canister did not return a proposal ID."),
+    }
+}
+
+/// Claims the neuron whose stake the controller has transferred to the staking subaccount for `memo`.
+///
+/// Returns the ID of the neuron.
+#[context("Failed to claim the neuron staked with memo {}.", memo)]
+pub async fn claim_neuron(agent: &Agent, controller: Principal, memo: u64) -> anyhow::Result<u64> {
+    let command = Command::ClaimOrRefresh(ClaimOrRefresh {
+        by: Some(By::MemoAndController(MemoAndController {
+            memo,
+            controller: Some(controller),
+        })),
+    });
+    match manage_neuron(agent, None, command).await? {
+        CommandResponse::ClaimOrRefresh(ClaimOrRefreshResponse {
+            refreshed_neuron_id: Some(NeuronId { id }),
+        }) => Ok(id),
+        _ => bail!("The governance canister did not return a neuron ID."),
+    }
+}
+
+/// Lists the neurons that the caller controls or is a hot key of.
+#[context("Failed to list neurons.")]
+pub async fn list_neurons(agent: &Agent) -> anyhow::Result<Vec<(u64, NeuronInfo)>> {
+    let governance_id = Principal::from_text(NNS_GOVERNANCE.canister_id)?;
+    let arg = ListNeurons {
+        neuron_ids: Vec::new(),
+        include_neurons_readable_by_caller: true,
+    };
+    let response = agent
+        .query(&governance_id, "list_neurons")
+        .with_arg(Encode!(&arg)?)
+        .call()
+        .await?;
+    let mut neurons = Decode!(&response, ListNeuronsResponse)?.neuron_infos;
+    neurons.sort_by_key(|(id, _)| *id);
+    Ok(neurons)
+}
+
+/// Increases the dissolve delay of a neuron.
+#[context("Failed to increase the dissolve delay of neuron {}.", neuron_id)]
+pub async fn increase_dissolve_delay(
+    agent: &Agent,
+    neuron_id: u64,
+    additional_dissolve_delay_seconds: u32,
+) -> anyhow::Result<()> {
+    configure_neuron(
+        agent,
+        neuron_id,
+        Operation::IncreaseDissolveDelay(IncreaseDissolveDelay {
+            additional_dissolve_delay_seconds,
+        }),
+    )
+    .await
+}
+
+/// Starts dissolving a neuron.
+#[context("Failed to start dissolving neuron {}.", neuron_id)]
+pub async fn start_dissolving(agent: &Agent, neuron_id: u64) -> anyhow::Result<()> {
+    configure_neuron(
+        agent,
+        neuron_id,
+        Operation::StartDissolving(StartDissolving {}),
+    )
+    .await
+}
+
+/// Stops dissolving a neuron.
+#[context("Failed to stop dissolving neuron {}.", neuron_id)]
+pub async fn stop_dissolving(agent: &Agent, neuron_id: u64) -> anyhow::Result<()> {
+    configure_neuron(
+        agent,
+        neuron_id,
+        Operation::StopDissolving(StopDissolving {}),
+    )
+    .await
+}
+
+/// Transfers the stake of a dissolved neuron to an account.
+///
+/// Returns the ledger block of the transfer.
+#[context("Failed to disburse neuron {}.", neuron_id)]
+pub async fn disburse_neuron(
+    agent: &Agent,
+    neuron_id: u64,
+    to_account: Option<AccountIdentifier>,
+    amount: Option<ICPTs>,
+) -> anyhow::Result<u64> {
+    let command = Command::Disburse(Disburse {
+        to_account: to_account.map(GovernanceAccountIdentifier::from),
+        amount,
+    });
+    match manage_neuron(agent, Some(neuron_id), command).await? {
+        CommandResponse::Disburse(DisburseResponse {
+            transfer_block_height,
+        }) => Ok(transfer_block_height),
+        _ => bail!("The governance canister did not return a ledger block."),
+    }
+}
+
+/// Sets the neurons that a neuron follows on a topic.
+#[context("Failed to set the followees of neuron {}.", neuron_id)]
+pub async fn follow(
+    agent: &Agent,
+    neuron_id: u64,
+    topic: i32,
+    followees: &[u64],
+) -> anyhow::Result<()> {
+    let command = Command::Follow(Follow {
+        topic,
+        followees: followees.iter().map(|&id| NeuronId { id }).collect(),
+    });
+    manage_neuron(agent, Some(neuron_id), command).await?;
+    Ok(())
+}
+
+/// Splits a neuron, moving `amount` of its stake to a new neuron.
+///
+/// Returns the ID of the new neuron.
+#[context("Failed to split neuron {}.", neuron_id)]
+pub async fn split_neuron(agent: &Agent, neuron_id: u64, amount: ICPTs) -> anyhow::Result<u64> {
+    let command = Command::Split(Split {
+        amount_e8s: amount.get_e8s(),
+    });
+    match manage_neuron(agent, Some(neuron_id), command).await? {
+        CommandResponse::Split(SplitResponse {
+            created_neuron_id: Some(NeuronId { id }),
+        }) => Ok(id),
+        _ => bail!("The governance canister did not return the ID of the new neuron."),
+    }
+}
+
+/// Changes the configuration of a neuron.
+async fn configure_neuron(
+    agent: &Agent,
+    neuron_id: u64,
+    operation: Operation,
+) -> anyhow::Result<()> {
+    let command = Command::Configure(Configure {
+        operation: Some(operation),
+    });
+    manage_neuron(agent, Some(neuron_id), command).await?;
+    Ok(())
+}
+
+/// Executes a command with a neuron.
+///
+/// # Errors
+/// - Returns an error if the call fails or the governance canister reports an error.
+async fn manage_neuron(
+    agent: &Agent,
+    neuron_id: Option<u64>,
+    command: Command,
+) -> anyhow::Result<CommandResponse> {
+    let governance_id = Principal::from_text(NNS_GOVERNANCE.canister_id)?;
+    let arg = ManageNeuron {
+        id: neuron_id.map(|id| NeuronId { id }),
+        command: Some(command),
+    };
+    let response = agent
+        .update(&governance_id, "manage_neuron")
+        .with_arg(Encode!(&arg)?)
+        .call_and_wait()
+        .await?;
+    match Decode!(&response, ManageNeuronResponse)?.command {
+        Some(CommandResponse::Error(error)) => {
+            bail!(
+                "The governance canister returned an error: {}",
+                error.error_message
+            )
+        }
+        Some(response) => Ok(response),
+        None => bail!("The governance canister returned no result."),
+    }
+}
+
+/// Waits until a proposal has been rejected, executed, or has failed.
+///
+/// # Errors
+/// - Returns an error if the proposal does not exist or is not decided in time.
+#[context("Failed to wait for proposal {}.", proposal_id)]
+pub async fn wait_for_proposal(agent: &Agent, proposal_id: u64) -> anyhow::Result<ProposalOutcome> {
+    let governance_id = Principal::from_text(NNS_GOVERNANCE.canister_id)?;
+    let mut retry_policy = ExponentialBackoff {
+        max_elapsed_time: Some(Duration::from_secs(120)),
+        ..ExponentialBackoff::default()
+    };
+    loop {
+        let response = agent
+            .query(&governance_id, "get_proposal_info")
+            .with_arg(Encode!(&proposal_id)?)
+            .call()
+            .await?;
+        let info = Decode!(&response, Option<ProposalInfo>)?
+            .ok_or_else(|| anyhow!("Proposal {proposal_id} does not exist."))?;
+        if info.executed_timestamp_seconds > 0 {
+            return Ok(ProposalOutcome::Executed);
+        }
+        if info.failed_timestamp_seconds > 0 {
+            return Ok(ProposalOutcome::Failed(
+                info.failure_reason
+                    .map(|reason| reason.error_message)
+                    .unwrap_or_else(|| "no reason given".to_string()),
+            ));
+        }
+        if info.status == 2 {
+            return Ok(ProposalOutcome::Rejected);
+        }
+        match retry_policy.next_backoff() {
+            Some(duration) => tokio::time::sleep(duration).await,
+            None => bail!("Proposal {proposal_id} was not decided in time."),
+        }
+    }
+}
@@ -0,0 +1,250 @@
+//! The project-level configuration of `dfx nns install`, read from `nns.json` next to `dfx.json`.
+//!
+//! Every setting may also be given on the command line.  Lists given on the command line are added to those in the
+//! file; other settings given on the command line take precedence.
+#![warn(missing_docs)]
+#![warn(clippy::missing_docs_in_private_items)]
+
+use crate::commands::install::InstallOpts;
+use crate::install_nns::{BtcNetwork, Config};
+use crate::install_plan::InstallProfile;
+use crate::neurons::NeuronSpec;
+use crate::test_tokens::{load_test_tokens, validate_test_tokens, TestToken};
+use dfx_extensions_utils::{nns_ic_commit, NNS_FRONTEND};
+
+use anyhow::{bail, Context};
+use fn_error_context::context;
+use ic_agent::export::Principal;
+use reqwest::Url;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// The name of the configuration file, which is read from the project root.
+pub const INSTALL_CONFIG_FILE_NAME: &str = "nns.json";
+
+/// How `dfx nns install` sets up the local NNS.
+///
+/// The field names match the command line flags, with underscores instead of dashes.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InstallConfig {
+    /// The IC commit the NNS wasms are taken from.  `--ic-commit` and `DFX_IC_COMMIT` take precedence.
+    #[serde(default)]
+    pub ic_commit: Option<String>,
+    /// Ledger accounts funded with test ICP, in addition to the built-in test accounts.
+    #[serde(default)]
+    pub ledger_accounts: Vec<String>,
+    /// Which NNS components to install.
+    #[serde(default)]
+    pub profile: Option<InstallProfile>,
+    /// Canisters not to install.
+    #[serde(default)]
+    pub skip: Vec<String>,
+    /// Whether to install the cycles ledger.
+    #[serde(default)]
+    pub with_cycles_ledger: bool,
+    /// Whether to install the ckBTC ledger, index and minter.
+    #[serde(default)]
+    pub with_ckbtc: bool,
+    /// The bitcoin network used by the ckBTC minter.
+    #[serde(default)]
+    pub ckbtc_network: Option<BtcNetwork>,
+    /// Neurons to create, in addition to the test neurons.
+    #[serde(default)]
+    pub neurons: Vec<NeuronSpec>,
+    /// ICRC-1 test tokens to install instead of the ckETH ledger.
+    #[serde(default)]
+    pub test_tokens: Vec<TestToken>,
+    /// URLs from which to download frontend wasms instead of the pinned releases, by canister name.
+    #[serde(default)]
+    pub frontend_wasm_urls: BTreeMap<String, String>,
+    /// nns-dapp feature flags, overriding the defaults.
+    #[serde(default)]
+    pub nns_dapp_feature_flags: BTreeMap<String, bool>,
+    /// The subnet in which SNS canisters are created, instead of the SNS subnet of the local network.
+    #[serde(default)]
+    pub sns_subnet: Option<Principal>,
+    /// The subnets the cycles minting canister creates canisters in, instead of the application subnets.
+    #[serde(default)]
+    pub app_subnets: Vec<Principal>,
+    /// The ICP/XDR conversion rate to set in the cycles minting canister, in XDR per ICP.
+    #[serde(default)]
+    pub icp_xdr_rate: Option<Decimal>,
+    /// How often the sns-aggregator polls the SNS canisters.
+    #[serde(default)]
+    pub sns_aggregator: Config,
+}
+
+impl InstallConfig {
+    /// Reads `nns.json` from the project root.  Without the file, the defaults are used.
+    #[context("Failed to read the NNS install configuration from '{}'.", project_root.join(INSTALL_CONFIG_FILE_NAME).display())]
+    pub fn load(project_root: &Path) -> anyhow::Result<Self> {
+        let path = project_root.join(INSTALL_CONFIG_FILE_NAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let json =
+            fs::read_to_string(&path).with_context(|| format!("Unable to read {:?}", path))?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Adds the settings given on the command line.
+    ///
+    /// # Errors
+    /// - Returns an error if the combined settings are inconsistent.
+    /// - Returns an error if `--only` is given while the file sets `profile` or `skip`.
+    pub fn apply_command_line(&mut self, opts: &InstallOpts) -> anyhow::Result<()> {
+        if !opts.only.is_empty() && (self.profile.is_some() || !self.skip.is_empty()) {
+            bail!("--only cannot be combined with the 'profile' or 'skip' settings of {INSTALL_CONFIG_FILE_NAME}.  Remove them from {INSTALL_CONFIG_FILE_NAME} to choose the canisters with --only.");
+        }
+        self.ic_commit = opts.ic_commit.clone().or(self.ic_commit.take());
+        self.ledger_accounts
+            .extend_from_slice(&opts.ledger_accounts);
+        self.profile = opts.profile.or(self.profile);
+        self.skip.extend_from_slice(&opts.skip);
+        self.neurons.extend_from_slice(&opts.neuron);
+        self.with_cycles_ledger |= opts.with_cycles_ledger;
+        self.with_ckbtc |= opts.with_ckbtc;
+        self.ckbtc_network = opts.ckbtc_network.or(self.ckbtc_network);
+        self.test_tokens.extend_from_slice(&opts.test_token);
+        self.sns_subnet = opts.sns_subnet.or(self.sns_subnet);
+        self.app_subnets.extend_from_slice(&opts.app_subnet);
+        self.icp_xdr_rate = opts.icp_xdr_rate.or(self.icp_xdr_rate);
+        if let Some(path) = &opts.test_tokens_file {
+            self.test_tokens.extend(load_test_tokens(path)?);
+        }
+        self.validate()
+    }
+
+    /// Checks that the settings are consistent.
+    fn validate(&self) -> anyhow::Result<()> {
+        validate_test_tokens(&self.test_tokens)?;
+        if self.ckbtc_network.is_some() && !self.with_ckbtc {
+            bail!("A ckBTC network is set but ckBTC is not installed.  Set with_ckbtc or pass --with-ckbtc.");
+        }
+        for canister_name in self.frontend_wasm_urls.keys() {
+            if !NNS_FRONTEND
+                .iter()
+                .any(|canister| canister.canister_name == canister_name)
+            {
+                bail!(
+                    "Unknown frontend canister '{canister_name}' in frontend_wasm_urls.  Choose from: {}",
+                    NNS_FRONTEND
+                        .iter()
+                        .map(|canister| canister.canister_name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+            self.frontend_wasm_url(canister_name)?;
+        }
+        self.icp_xdr_permyriad()?;
+        Ok(())
+    }
+
+    /// The URL from which to download the wasm of a frontend canister, if it is not the pinned release.
+    pub fn frontend_wasm_url(&self, canister_name: &str) -> anyhow::Result<Option<Url>> {
+        self.frontend_wasm_urls
+            .get(canister_name)
+            .map(|url| {
+                Url::parse(url)
+                    .with_context(|| format!("Could not parse url for {canister_name} wasm: {url}"))
+            })
+            .transpose()
+    }
+
+    /// The ICP/XDR conversion rate to set, in the ten-thousandths of an XDR per ICP that the CMC works with.
+    ///
+    /// # Errors
+    /// - Returns an error if the rate is not positive or has more than four decimal places.
+    pub fn icp_xdr_permyriad(&self) -> anyhow::Result<Option<u64>> {
+        self.icp_xdr_rate.map(icp_xdr_permyriad).transpose()
+    }
+
+    /// The IC commit the NNS wasms are taken from.
+    pub fn ic_commit(&self) -> String {
+        self.ic_commit.clone().unwrap_or_else(nns_ic_commit)
+    }
+
+    /// The components to install.
+    pub fn profile(&self) -> InstallProfile {
+        self.profile.unwrap_or_default()
+    }
+
+    /// The bitcoin network for the ckBTC canisters, if they are to be installed.
+    pub fn ckbtc(&self) -> Option<BtcNetwork> {
+        self.with_ckbtc
+            .then(|| self.ckbtc_network.unwrap_or(BtcNetwork::Regtest))
+    }
+
+    /// The nns-dapp feature flags, as a JSON object.
+    pub fn nns_dapp_feature_flags(&self) -> anyhow::Result<String> {
+        let mut flags = BTreeMap::from([
+            ("ENABLE_CKBTC".to_string(), self.ckbtc().is_some()),
+            ("ENABLE_CKTESTBTC".to_string(), false),
+            ("ENABLE_HIDE_ZERO_BALANCE".to_string(), true),
+            ("ENABLE_VOTING_INDICATION".to_string(), true),
+        ]);
+        flags.extend(self.nns_dapp_feature_flags.clone());
+        Ok(serde_json::to_string(&flags)?)
+    }
+}
+
+/// Converts an ICP/XDR conversion rate, in XDR per ICP, to the ten-thousandths of an XDR per ICP that the NNS works with.
+///
+/// # Errors
+/// - Returns an error if the rate is not positive or has more than four decimal places.
+pub fn icp_xdr_permyriad(rate: Decimal) -> anyhow::Result<u64> {
+    let permyriad = rate * Decimal::from(10_000);
+    if permyriad <= Decimal::ZERO || !permyriad.fract().is_zero() {
+        bail!("Invalid ICP/XDR conversion rate {rate}.  The rate must be positive, with at most four decimal places.");
+    }
+    u64::try_from(permyriad)
+        .with_context(|| format!("The ICP/XDR conversion rate {rate} is too large."))
+}
+
+#[cfg(test)]
+/// Tests of combining the file with the command line.
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    /// The options of `dfx nns install` with these arguments.
+    fn opts(args: &[&str]) -> InstallOpts {
+        InstallOpts::parse_from(["install"].iter().chain(args))
+    }
+
+    #[test]
+    /// `--only` would silently override the components chosen in the file, so the combination is rejected.
+    fn only_conflicts_with_the_file_selection() {
+        let only = opts(&["--only", "internet_identity"]);
+
+        let mut config = InstallConfig {
+            skip: vec!["nns-icp-index".to_string()],
+            ..InstallConfig::default()
+        };
+        let err = config.apply_command_line(&only).unwrap_err();
+        assert!(err.to_string().contains("--only cannot be combined"));
+
+        let mut config = InstallConfig {
+            profile: Some(InstallProfile::Core),
+            ..InstallConfig::default()
+        };
+        assert!(config.apply_command_line(&only).is_err());
+
+        let mut config = InstallConfig::default();
+        config.apply_command_line(&only).unwrap();
+
+        let mut config = InstallConfig {
+            skip: vec!["nns-icp-index".to_string()],
+            ..InstallConfig::default()
+        };
+        config
+            .apply_command_line(&opts(&["--skip", "internet_identity"]))
+            .unwrap();
+        assert_eq!(config.skip, ["nns-icp-index", "internet_identity"]);
+    }
+}
@@ -10,39 +10,46 @@ use dfx_core::config::model::dfinity::{NetworksConfig, ReplicaSubnetType};
 use dfx_core::config::model::network_descriptor::NetworkDescriptor;
 use dfx_core::identity::CallSender;
 use dfx_core::{canister::install_canister_wasm, error::cli::UserConsent};
-use dfx_extensions_utils::dependencies::download_wasms::nns::{
-    ICP_INDEX, ICRC1_INDEX, ICRC1_LEDGER, INTERNET_IDENTITY, NNS_DAPP, NNS_LEDGER, SNS_AGGREGATOR,
-};
+use dfx_extensions_utils::dependencies::download_wasms::nns::NNS_CYCLES_MINTING;
 use dfx_extensions_utils::{
-    call_extension_bundled_binary, download_nns_wasms, nns_wasm_dir, IcNnsInitCanister,
-    SnsCanisterInstallation, StandardCanister, ED25519_TEST_ACCOUNT, NNS_CORE, NNS_CORE_MANUAL,
-    NNS_FRONTEND, NNS_SNS_WASM, SECP256K1_TEST_ACCOUNT, SNS_CANISTERS,
+    download_gz, download_nns_wasms, download_sns_wasms, ic_repo_wasm_sha256_sums,
+    ic_repo_wasm_url, nns_wasm_dir, verify_wasms_are_present, SnsCanisterInstallation, Step,
+    ED25519_TEST_ACCOUNT, NNS_FRONTEND, NNS_SNS_WASM, SECP256K1_TEST_ACCOUNT, SNS_CANISTERS,
 };
 use ic_sns_cli::{add_sns_wasm_for_tests, AddSnsWasmForTestsArgs};
 
+use crate::canister_state::{canister_state, file_sha256, CanisterState};
+use crate::commands::install::InstallOpts;
+use crate::commands::OutputFormat;
+use crate::governance::{
+    execute_nns_function, test_neuron_agent, NnsFunction, SetAuthorizedSubnetworkListArgs,
+    UpdateIcpXdrConversionRatePayload, UpdateIcpXdrConversionRatePayloadReason,
+};
+use crate::install_config::InstallConfig;
+use crate::install_plan::{InstallPlan, PlannedCanister, Subnets};
+use crate::install_state::InstallState;
+use crate::neurons::seed_neurons;
+use crate::nns_init::{nns_init, NnsInitOpts};
+
 use anyhow::{anyhow, bail, Context};
-use backoff::backoff::Backoff;
-use backoff::ExponentialBackoff;
-use candid::{CandidType, Encode};
+use candid::{CandidType, Decode, Encode};
+use clap::ValueEnum;
 use fn_error_context::context;
 use futures_util::future::try_join_all;
+use futures_util::stream::{self, StreamExt, TryStreamExt};
 use ic_agent::export::Principal;
 use ic_agent::Agent;
-use ic_icp_index::{IndexArg as IcpIndexArg, InitArg as IcpInitArg};
-use ic_icrc1_index_ng::{IndexArg, InitArg as IndexInitArg};
-use ic_icrc1_ledger::{InitArgsBuilder, LedgerArgument};
 use ic_utils::interfaces::management_canister::builders::CanisterInstallMode;
 use ic_utils::interfaces::ManagementCanister;
 use pocket_ic::common::rest::Topology;
 use reqwest::Url;
-use serde::Serialize;
-use slog::Logger;
-use std::ffi::OsString;
+use rust_decimal::Decimal;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use slog::{info, o, warn, Logger};
 use std::fs;
-use std::io::Write;
-use std::path::Component;
 use std::path::{Path, PathBuf};
-use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Init and post_upgrade arguments for NNS frontend dapp.
 #[derive(Debug, Eq, PartialEq, CandidType, Serialize)]
@@ -55,20 +62,92 @@ pub struct CanisterArguments {
     pub schema: Option<SchemaLabel>,
 }
 
-/// Init and post_upgrade arguments for SNS aggregator.
+/// Arguments for the cycles ledger.
+#[derive(Debug, Eq, PartialEq, CandidType, Serialize)]
+pub enum CyclesLedgerArgs {
+    /// Arguments for a new cycles ledger.
+    Init(CyclesLedgerInitArgs),
+}
+/// Init arguments for the cycles ledger.
+#[derive(Debug, Eq, PartialEq, CandidType, Serialize)]
+pub struct CyclesLedgerInitArgs {
+    /// The maximum number of blocks returned by one call to `icrc3_get_blocks`.
+    pub max_blocks_per_request: u64,
+    /// The cycles ledger index canister, if any.
+    pub index_id: Option<Principal>,
+}
+
+/// The bitcoin network that the local ckBTC minter uses.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, CandidType, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BtcNetwork {
+    /// A local regtest network, such as the one served by `dfx start --enable-bitcoin`.
+    Regtest,
+    /// The bitcoin testnet.
+    Testnet,
+}
+/// Arguments for the ckBTC minter.
+#[derive(Debug, Eq, PartialEq, CandidType, Serialize)]
+pub enum CkBtcMinterArgs {
+    /// Arguments for a new minter.
+    Init(CkBtcMinterInitArgs),
+}
+/// Who may use the ckBTC minter.
+#[derive(Debug, Eq, PartialEq, CandidType, Serialize)]
+pub enum CkBtcMinterMode {
+    /// Anyone may deposit and withdraw.
+    GeneralAvailability,
+}
+/// Init arguments for the ckBTC minter.
 #[derive(Debug, Eq, PartialEq, CandidType, Serialize)]
+pub struct CkBtcMinterInitArgs {
+    /// The bitcoin network; the minter calls the bitcoin canister of this network.
+    pub btc_network: BtcNetwork,
+    /// The ckBTC ledger.
+    pub ledger_id: Principal,
+    /// The name of the threshold ECDSA key that holds the deposited bitcoin.
+    pub ecdsa_key_name: String,
+    /// The smallest amount, in satoshi, that may be withdrawn.
+    pub retrieve_btc_min_amount: u64,
+    /// How long withdrawals are batched before they are sent.
+    pub max_time_in_queue_nanos: u64,
+    /// How many confirmations a deposit needs.
+    pub min_confirmations: Option<u32>,
+    /// Who may use the minter.
+    pub mode: CkBtcMinterMode,
+    /// The fee, in satoshi, for checking deposits.
+    pub check_fee: Option<u64>,
+    /// The canister that checks deposits, if any.
+    pub btc_checker_principal: Option<Principal>,
+}
+
+/// Init and post_upgrade arguments for SNS aggregator.
+#[derive(Clone, Debug, Eq, PartialEq, CandidType, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub update_interval_ms: u64,
     pub fast_interval_ms: u64,
 }
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            update_interval_ms: 1_000,
+            fast_interval_ms: 100,
+        }
+    }
+}
 
 /// Installs NNS canisters on a local dfx server.
+///
+/// With `--dry-run`, the installation plan is printed instead and nothing is changed.
+///
+/// The installation is resumable: steps whose result is already present on the network are skipped, so an
+/// installation that failed halfway can be completed by running `dfx nns install` again.
 /// # Notes:
-///   - Set DFX_IC_NNS_INIT_PATH=<path to binary> to use a different &binary for local development
 ///   - This won't work with an HSM, because the agent holds a session open
 ///   - The provider_url is what the agent connects to, and forwards to the replica.
 /// # Prerequisites
-///   - There must be no canisters already present in the dfx server.
+///   - The NNS canister IDs must be either unused or taken by a previous run of `dfx nns install`.
 ///   - The dfx server must be running as subnet type system; this is set in the local network setting in dfx.json and
 ///     will normally be different from the production network type, which will most
 ///     likely be "application".
@@ -84,10 +163,11 @@ pub async fn install_nns(
     network: &NetworkDescriptor,
     networks_config: &NetworksConfig,
     dfx_cache_path: &Path,
-    ledger_accounts: &[String],
-    _logger: &Logger,
+    opts: &InstallOpts,
+    config: &InstallConfig,
+    logger: &Logger,
 ) -> anyhow::Result<()> {
-    eprintln!("Checking out the environment...");
+    info!(logger, "Checking out the environment...");
     // Retrieve the PocketIC instance topology.
     let topology = if let Some(descriptor) = &network.local_server_descriptor {
         let endpoint = format!("http://{}/_/topology", descriptor.bind_address);
@@ -104,188 +184,336 @@ pub async fn install_nns(
     if topology.is_none() {
         verify_local_replica_type_is_system(network, networks_config)?;
     }
-    verify_nns_canister_ids_are_available(agent).await?;
     let provider_url = get_and_check_provider(network)?;
-    let nns_url = provider_url.clone();
     let root_subnet_id = get_subnet_id(agent).await?;
-    let sns_subnet_id = topology
-        .as_ref()
-        .and_then(|topology| topology.get_sns())
-        .unwrap_or(root_subnet_id);
-    let default_subnet_id = topology
-        .as_ref()
-        .and_then(|topology| {
-            topology
-                .get_app_subnets()
-                .first()
-                .cloned()
-                .or_else(|| topology.get_system_subnets().first().cloned())
-        })
-        .unwrap_or(root_subnet_id);
+    let subnets = place_subnets(topology.as_ref(), root_subnet_id, config)?;
 
-    eprintln!("Installing the core backend wasm canisters...");
-    download_nns_wasms(dfx_cache_path).await?;
     let mut test_accounts = vec![
         ED25519_TEST_ACCOUNT.to_string(),
         SECP256K1_TEST_ACCOUNT.to_string(),
     ];
-    test_accounts.extend_from_slice(ledger_accounts);
-    let ic_nns_init_opts = IcNnsInitOpts {
-        wasm_dir: nns_wasm_dir(dfx_cache_path),
-        nns_url: nns_url.to_string(),
+    test_accounts.extend_from_slice(&config.ledger_accounts);
+    let wasm_dir = opts
+        .wasm_dir
+        .clone()
+        .unwrap_or_else(|| nns_wasm_dir(dfx_cache_path));
+    let nns_init_opts = NnsInitOpts {
         test_accounts,
-        sns_subnets: Some(sns_subnet_id.to_string()),
+        sns_subnets: vec![subnets.sns],
         local_registry_file: network.local_server_descriptor.as_ref().map(|desc| {
             desc.data_dir_by_settings_digest()
                 .join("state/replicated_state/registry.proto")
         }),
+        neurons: seed_neurons(&config.neurons).await?,
     };
-    ic_nns_init(&ic_nns_init_opts, dfx_cache_path).await?;
+    let mut plan = InstallPlan::new(wasm_dir, nns_init_opts, &provider_url, &subnets, config)?;
+    plan.select_canisters(config.profile(), &config.skip, &opts.only)?;
+    verify_force_reinstall_canisters(&plan, &opts.force_reinstall)?;
+    if opts.dry_run {
+        plan.print();
+        return Ok(());
+    }
 
-    eprintln!("Uploading NNS configuration data...");
-    upload_nns_sns_wasms_canister_wasms(dfx_cache_path)?;
+    // Without a network connection, every file has to be present before anything is changed.
+    let offline = opts.wasm_dir.is_some();
+    if offline {
+        verify_wasms_are_present(&plan.wasm_dir, &plan.wasm_names())?;
+    }
 
-    // Install manual backend canisters:
-    for IcNnsInitCanister {
-        wasm_name,
-        canister_name,
-        canister_id,
-        ..
-    } in NNS_CORE_MANUAL
-    {
-        let local_wasm_path = nns_wasm_dir(dfx_cache_path).join(wasm_name);
-        let specified_id = Principal::from_text(canister_id)?;
-        let arg = if *canister_id == ICRC1_LEDGER.canister_id {
-            let cketh_ledger_args = InitArgsBuilder::for_tests()
-                .with_token_symbol("ckETH".to_string())
-                .with_token_name("ckETH".to_string())
-                .build();
-            Some(Encode!(&(LedgerArgument::Init(cketh_ledger_args))).unwrap())
-        } else if *canister_id == ICRC1_INDEX.canister_id {
-            let cketh_index_args = IndexArg::Init(IndexInitArg {
-                ledger_id: Principal::from_str(ICRC1_LEDGER.canister_id).unwrap(),
-                retrieve_blocks_from_ledger_interval_seconds: None,
-            });
-            Some(Encode!(&Some(cketh_index_args)).unwrap())
-        } else if *canister_id == ICP_INDEX.canister_id {
-            let icp_index_args = IcpIndexArg::Init(IcpInitArg {
-                ledger_id: Principal::from_str(NNS_LEDGER.canister_id).unwrap(),
-                retrieve_blocks_from_ledger_interval_seconds: None,
-            });
-            Some(Encode!(&Some(icp_index_args)).unwrap())
-        } else {
-            None
-        };
-        let installed_canister_id = install_canister(
-            network,
-            agent,
-            canister_name,
-            &local_wasm_path,
-            specified_id,
-            arg.as_deref(),
-        )
-        .await?
-        .to_text();
-        if canister_id != &installed_canister_id {
-            bail!("Canister '{canister_name}' was installed at an incorrect canister ID.  Expected '{canister_id}' but got '{installed_canister_id}'.");
+    let step = Step::start(logger, "Installing the core backend wasm canisters");
+    if !offline {
+        download_nns_wasms(logger, &plan.ic_commit, dfx_cache_path).await?;
+        if plan.upload_sns_wasms {
+            download_sns_wasms(logger, &plan.ic_commit, &plan.wasm_dir).await?;
         }
     }
-    // Install the GUI canisters:
-    for StandardCanister {
-        wasm_url,
-        wasm_name,
-        canister_name,
-        canister_id,
-    } in NNS_FRONTEND
+    let core_states = core_canister_states(agent, &plan).await?;
+    nns_init(
+        logger,
+        agent,
+        &plan.wasm_dir,
+        &plan.core_canisters,
+        &core_states,
+    )
+    .await?;
+    step.finish();
+
+    if plan.upload_sns_wasms {
+        let step = Step::start(logger, "Uploading NNS configuration data");
+        upload_nns_sns_wasms_canister_wasms(logger, agent, &plan.wasm_dir).await?;
+        step.finish();
+    }
+
+    // Install the remaining backend canisters and the GUI canisters, several at a time:
+    let jobs = opts.jobs.get();
+    if !offline {
+        let wasm_dir = &plan.wasm_dir;
+        let ic_commit = &plan.ic_commit;
+        let sha256_sums = &ic_repo_wasm_sha256_sums(logger, ic_commit).await?;
+        let mut downloads: Vec<&PlannedCanister> = Vec::new();
+        for canister in &plan.canisters {
+            // Canisters that share a wasm must not download it to the same file at the same time.
+            if !downloads
+                .iter()
+                .any(|download| download.wasm_name == canister.wasm_name)
+            {
+                downloads.push(canister);
+            }
+        }
+        stream::iter(downloads)
+            .map(|canister| async move {
+                let local_wasm_path = wasm_dir.join(&canister.wasm_name);
+                let expected_sha256 = match canister.wasm_sha256 {
+                    Some(wasm_sha256) => Some(wasm_sha256),
+                    // Wasms from the IC repository are verified against the hashes published for the IC commit.
+                    None if canister.wasm_url
+                        == ic_repo_wasm_url(&canister.wasm_name, ic_commit)? =>
+                    {
+                        Some(sha256_sums.get(&format!("{}.gz", canister.wasm_name))?)
+                    }
+                    None => None,
+                };
+                download_gz(
+                    logger,
+                    &canister.wasm_url,
+                    &local_wasm_path,
+                    expected_sha256,
+                )
+                .await
+            })
+            .buffer_unordered(jobs)
+            .try_collect::<Vec<()>>()
+            .await?;
+    }
+    for wave in plan.install_waves()? {
+        stream::iter(wave)
+            .map(|canister| {
+                let force_reinstall = opts.force_reinstall.contains(&canister.canister_name);
+                install_planned_canister(
+                    logger,
+                    network,
+                    agent,
+                    &plan.wasm_dir,
+                    canister,
+                    force_reinstall,
+                )
+            })
+            .buffer_unordered(jobs)
+            .try_collect::<Vec<()>>()
+            .await?;
+    }
+    // ... and configure the backend NNS canisters:
+    let step = Step::start(logger, "Configuring the NNS");
+    let authorized_subnets = cmc_default_subnets(agent).await?;
+    if plan
+        .cmc_subnet_ids
+        .iter()
+        .all(|subnet| authorized_subnets.contains(subnet))
     {
-        let local_wasm_path = nns_wasm_dir(dfx_cache_path).join(wasm_name);
-        let parsed_wasm_url = Url::parse(wasm_url)
-            .with_context(|| format!("Could not parse url for {canister_name} wasm: {wasm_url}"))?;
-        download(&parsed_wasm_url, &local_wasm_path).await?;
-        let specified_id = Principal::from_text(canister_id)?;
-        let arg = if *canister_id == NNS_DAPP.canister_id {
-            let nns_dapp_metadata = vec![
-                ("API_HOST".to_string(), "http://localhost:8080".to_string()),
-                ("CKETH_INDEX_CANISTER_ID".to_string(), ICRC1_INDEX.canister_id.to_string()),
-                ("CKETH_LEDGER_CANISTER_ID".to_string(), ICRC1_LEDGER.canister_id.to_string()),
-                ("CYCLES_MINTING_CANISTER_ID".to_string(), "rkp4c-7iaaa-aaaaa-aaaca-cai".to_string()),
-                ("DFX_NETWORK".to_string(), "local".to_string()),
-                ("FEATURE_FLAGS".to_string(), "{\"ENABLE_CKBTC\":false,\"ENABLE_CKTESTBTC\":false,\"ENABLE_HIDE_ZERO_BALANCE\":true,\"ENABLE_VOTING_INDICATION\":true}".to_string()),
-                ("FETCH_ROOT_KEY".to_string(), "true".to_string()),
-                ("GOVERNANCE_CANISTER_ID".to_string(), "rrkah-fqaaa-aaaaa-aaaaq-cai".to_string()),
-                ("HOST".to_string(), "http://localhost:8080".to_string()),
-                ("IDENTITY_SERVICE_URL".to_string(), format!("http://{}.localhost:8080", INTERNET_IDENTITY.canister_id)),
-                ("INDEX_CANISTER_ID".to_string(), ICP_INDEX.canister_id.to_string()),
-                ("LEDGER_CANISTER_ID".to_string(), "ryjl3-tyaaa-aaaaa-aaaba-cai".to_string()),
-                ("OWN_CANISTER_ID".to_string(), NNS_DAPP.canister_id.to_string()),
-                ("ROBOTS".to_string(), "<meta name=\"robots\" content=\"noindex, nofollow\" />".to_string()),
-                ("SNS_AGGREGATOR_URL".to_string(), format!("http://{}.localhost:8080", SNS_AGGREGATOR.canister_id)),
-                ("STATIC_HOST".to_string(), "http://localhost:8080".to_string()),
-                ("TVL_CANISTER_ID".to_string(), "".to_string()),
-                ("WASM_CANISTER_ID".to_string(), "qaa6y-5yaaa-aaaaa-aaafa-cai".to_string())
-            ];
-            let nns_dapp_init_args = Some(CanisterArguments {
-                args: nns_dapp_metadata,
-                schema: Some(SchemaLabel::AccountsInStableMemory),
-            });
-            Some(Encode!(&nns_dapp_init_args).unwrap())
-        } else if *canister_id == SNS_AGGREGATOR.canister_id {
-            Some(
-                Encode!(&Some(Config {
-                    update_interval_ms: 1_000,
-                    fast_interval_ms: 100,
-                }))
-                .unwrap(),
-            )
+        info!(logger, "The cycles minting canister is already authorized to create canisters on the application subnets.");
+    } else {
+        set_cmc_authorized_subnets(logger, &provider_url, &plan.cmc_subnet_ids).await?;
+    }
+    if let Some(xdr_permyriad_per_icp) = plan.icp_xdr_permyriad {
+        if cmc_icp_xdr_permyriad(agent).await? == xdr_permyriad_per_icp {
+            info!(
+                logger,
+                "The cycles minting canister already has the requested ICP/XDR conversion rate."
+            );
         } else {
-            None
+            set_icp_xdr_conversion_rate(logger, &provider_url, xdr_permyriad_per_icp).await?;
+        }
+    }
+    step.finish();
+
+    let details = nns_details(agent, &plan, &provider_url, subnets).await?;
+    InstallState::from_details(&plan, &details).save(network)?;
+    match opts.output {
+        OutputFormat::Table => print_nns_details(&details),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&details)?),
+    }
+    Ok(())
+}
+
+/// Chooses the subnets to install on, following the PocketIC topology where there is one.
+///
+/// The SNS and application subnets can be overridden with `--sns-subnet` and `--app-subnet`.  Without a topology,
+/// every canister goes on the subnet the agent talks to.
+///
+/// # Errors
+/// - Returns an error if an overriding subnet is not part of the local network.
+#[context("Failed to choose the subnets to install on.")]
+fn place_subnets(
+    topology: Option<&Topology>,
+    root_subnet_id: Principal,
+    config: &InstallConfig,
+) -> anyhow::Result<Subnets> {
+    for subnet in config.sns_subnet.iter().chain(&config.app_subnets) {
+        let exists = match topology {
+            Some(topology) => topology.subnet_configs.contains_key(subnet),
+            None => *subnet == root_subnet_id,
         };
-        let installed_canister_id = install_canister(
-            network,
-            agent,
-            canister_name,
-            &local_wasm_path,
-            specified_id,
-            arg.as_deref(),
-        )
-        .await?
-        .to_text();
-        if canister_id != &installed_canister_id {
-            bail!("Canister '{canister_name}' was installed at an incorrect canister ID.  Expected '{canister_id}' but got '{installed_canister_id}'.");
+        if !exists {
+            bail!("Subnet {subnet} is not part of the local network.");
         }
     }
-    // ... and configure the backend NNS canisters:
-    eprintln!("Configuring the NNS...");
-    set_cmc_authorized_subnets(&nns_url, &default_subnet_id.to_string(), dfx_cache_path)?;
+    let sns = config
+        .sns_subnet
+        .or_else(|| topology.and_then(|topology| topology.get_sns()))
+        .unwrap_or(root_subnet_id);
+    let app = if !config.app_subnets.is_empty() {
+        config.app_subnets.clone()
+    } else {
+        topology
+            .map(|topology| {
+                let app_subnets = topology.get_app_subnets();
+                if app_subnets.is_empty() {
+                    topology.get_system_subnets().into_iter().take(1).collect()
+                } else {
+                    app_subnets
+                }
+            })
+            .filter(|subnets: &Vec<Principal>| !subnets.is_empty())
+            .unwrap_or_else(|| vec![root_subnet_id])
+    };
+    Ok(Subnets {
+        nns: root_subnet_id,
+        ii: topology.and_then(|topology| topology.get_ii()),
+        fiduciary: topology.and_then(|topology| topology.get_fiduciary()),
+        sns,
+        app,
+    })
+}
 
-    print_nns_details(provider_url)?;
+/// Checks that the canisters passed to `--force-reinstall` are installed by `dfx nns install` itself.
+///
+/// The core canisters are installed together and controlled by the NNS root canister, so they cannot be reinstalled.
+fn verify_force_reinstall_canisters(
+    plan: &InstallPlan,
+    force_reinstall: &[String],
+) -> anyhow::Result<()> {
+    for canister_name in force_reinstall {
+        if !plan
+            .canisters
+            .iter()
+            .any(|canister| &canister.canister_name == canister_name)
+        {
+            bail!(
+                "Cannot reinstall '{canister_name}'.  Only these canisters can be reinstalled: {}",
+                plan.canisters
+                    .iter()
+                    .map(|canister| canister.canister_name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
     Ok(())
 }
 
+/// Reads the state of every core canister, in the order of `plan.core_canisters`.
+#[context("Failed to check which core NNS canisters are installed.")]
+async fn core_canister_states(
+    agent: &Agent,
+    plan: &InstallPlan,
+) -> anyhow::Result<Vec<CanisterState>> {
+    try_join_all(
+        plan.core_canisters
+            .iter()
+            .map(|canister| canister_state(agent, canister.canister_id)),
+    )
+    .await
+}
+
+/// Gets the subnets the CMC creates canisters in by default.
+#[context("Failed to get the default subnets of the cycles minting canister.")]
+async fn cmc_default_subnets(agent: &Agent) -> anyhow::Result<Vec<Principal>> {
+    let response = agent
+        .query(
+            &Principal::from_text(NNS_CYCLES_MINTING.canister_id)?,
+            "get_default_subnets",
+        )
+        .with_arg(Encode!()?)
+        .call()
+        .await?;
+    Ok(Decode!(&response, Vec<Principal>)?)
+}
+
+/// The ICP/XDR conversion rate of the cycles minting canister.
+#[derive(CandidType, Deserialize)]
+struct IcpXdrConversionRate {
+    /// The number of ten-thousandths of an XDR that one ICP is worth.
+    xdr_permyriad_per_icp: u64,
+}
+
+/// The part of the CMC's `get_icp_xdr_conversion_rate` response that is used here.
+#[derive(CandidType, Deserialize)]
+struct IcpXdrConversionRateResponse {
+    /// The current rate.
+    data: IcpXdrConversionRate,
+}
+
+/// Gets the ICP/XDR conversion rate of the CMC, in ten-thousandths of an XDR per ICP.
+#[context("Failed to get the ICP/XDR conversion rate of the cycles minting canister.")]
+async fn cmc_icp_xdr_permyriad(agent: &Agent) -> anyhow::Result<u64> {
+    let response = agent
+        .query(
+            &Principal::from_text(NNS_CYCLES_MINTING.canister_id)?,
+            "get_icp_xdr_conversion_rate",
+        )
+        .with_arg(Encode!()?)
+        .call()
+        .await?;
+    Ok(Decode!(&response, IcpXdrConversionRateResponse)?
+        .data
+        .xdr_permyriad_per_icp)
+}
+
 /// Gets and checks the provider URL
 ///
+/// Any port is supported; the frontend configuration is derived from the provider URL.
+///
 /// # Errors
 /// - The provider may be malformed.
-/// - Only provider localhost:8080 is supported; this is compiled into the canister IDs.
-///   - The port constraint may be eased, perhaps, at some stage.
-///   - The requirement that the domain root is 'localhost' is less likely to change; 127.0.0.1 doesn't support subdomains.
 #[context("Failed to get a valid provider for network '{}'.  Please check networks.json and dfx.json.", network_descriptor.name)]
-fn get_and_check_provider(network_descriptor: &NetworkDescriptor) -> anyhow::Result<Url> {
+pub fn get_and_check_provider(network_descriptor: &NetworkDescriptor) -> anyhow::Result<Url> {
     let provider_url = network_descriptor
         .first_provider()
         .with_context(|| "Environment has no providers")?;
     let provider_url: Url = Url::parse(provider_url)
-        .with_context(|| "Malformed provider URL in this environment: {url_str}")?;
-
-    if provider_url.port() != Some(8080) {
-        return Err(anyhow!(
-            "dfx nns install supports only port 8080, not {provider_url}. Please set the 'local' network's provider to '127.0.0.1:8080'."
-        ));
+        .with_context(|| format!("Malformed provider URL in this environment: {provider_url}"))?;
+    if provider_url.port_or_known_default().is_none() {
+        bail!("The provider URL {provider_url} has no port.");
     }
 
     Ok(provider_url)
 }
 
+/// The URL of the local provider as seen from a browser, e.g. `http://localhost:8080`.
+///
+/// The domain root is always 'localhost', as 127.0.0.1 doesn't support subdomains.
+pub fn browser_url(provider_url: &Url) -> anyhow::Result<Url> {
+    let mut url = provider_url.clone();
+    url.set_host(Some("localhost"))
+        .with_context(|| format!("Could not use localhost as the host of {provider_url}"))?;
+    url.set_path("");
+    Ok(url)
+}
+
+/// The URL at which the local provider serves a canister, e.g. `http://qsgjb-riaaa-aaaaa-aaaga-cai.localhost:8080`.
+pub fn canister_url(provider_url: &Url, canister_id: &str) -> anyhow::Result<Url> {
+    let mut url = browser_url(provider_url)?;
+    let host = format!("{}.localhost", canister_id);
+    url.set_host(Some(&host))
+        .with_context(|| "Could not add canister ID as a subdomain to localhost")?;
+    Ok(url)
+}
+
+/// A URL without the trailing slash, as expected in canister configuration.
+pub fn origin(url: &Url) -> String {
+    url.origin().ascii_serialization()
+}
+
 /// Gets the local replica URL.  Note: This is not the same as the provider URL.
 ///
 /// The replica URL hosts the canister dashboard and is used for installing NNS wasms.
@@ -329,57 +557,81 @@ async fn get_subnet_id(agent: &Agent) -> anyhow::Result<Principal> {
     Ok(Principal::self_authenticating(root_key))
 }
 
-/// The NNS canisters use the very first few canister IDs; they must be available.
-#[context("Failed to verify that the network is empty; dfx nns install must be run just after dfx start --clean")]
-async fn verify_nns_canister_ids_are_available(agent: &Agent) -> anyhow::Result<()> {
-    /// Checks that the canister is unused on the given network.
-    ///
-    /// The network is queried directly; local state such as canister_ids.json has no effect on this function.
-    async fn verify_canister_id_is_available(
-        agent: &Agent,
-        canister_id: &str,
-        canister_name: &str,
-    ) -> anyhow::Result<()> {
-        let canister_principal = Principal::from_text(canister_id).with_context(|| {
-            format!("Internal error: {canister_name} has an invalid canister ID: {canister_id}")
-        })?;
-        if agent
-            .read_state_canister_info(canister_principal, "module_hash")
-            .await
-            .is_ok()
-        {
-            return Err(anyhow!(
-                "The ID for the {canister_name} canister has already been taken."
-            ));
-        }
-        Ok(())
-    }
+/// What `dfx nns install` installed, as printed at the end of the installation.
+#[derive(Serialize)]
+pub struct NnsDetails {
+    /// The IC commit the NNS wasms were taken from.
+    pub ic_commit: String,
+    /// The backend canisters, core canisters first.
+    pub backend_canisters: Vec<CanisterDetails>,
+    /// The frontend canisters.
+    pub frontend_canisters: Vec<CanisterDetails>,
+    /// The ledger accounts that were funded with test ICP.
+    pub test_accounts: Vec<String>,
+    /// The subnets the canisters were installed on.
+    pub subnets: Subnets,
+}
 
-    try_join_all(NNS_CORE.iter().cloned().map(
-        |IcNnsInitCanister {
-             canister_id,
-             canister_name,
-             ..
-         }| verify_canister_id_is_available(agent, canister_id, canister_name),
-    ))
-    .await?;
-    Ok(())
+/// An installed canister.
+#[derive(Serialize)]
+pub struct CanisterDetails {
+    /// The name of the canister, as used by `dfx nns install`.
+    pub name: String,
+    /// The ID of the canister.
+    pub canister_id: Principal,
+    /// The subnet the canister is installed on.
+    pub subnet_id: Principal,
+    /// The URL at which the local network serves the canister.
+    pub url: String,
+    /// The hex encoded SHA-256 hash of the wasm module the canister runs, if any.
+    pub module_hash: Option<String>,
 }
 
-/// Provides the user with a printout detailing what has been installed for them.
+/// Collects the details of what has been installed.
 ///
 /// # Errors
 /// - May fail if the provider URL is invalid.
-#[context("Failed to print NNS details.")]
-fn print_nns_details(provider_url: Url) -> anyhow::Result<()> {
-    let canister_url = |canister_id: &str| -> anyhow::Result<String> {
-        let mut url = provider_url.clone();
-        let host = format!("{}.localhost", canister_id);
-        url.set_host(Some(&host))
-            .with_context(|| "Could not add canister ID as a subdomain to localhost")?;
-        Ok(url.to_string())
-    };
+#[context("Failed to collect the NNS details.")]
+async fn nns_details(
+    agent: &Agent,
+    plan: &InstallPlan,
+    provider_url: &Url,
+    subnets: Subnets,
+) -> anyhow::Result<NnsDetails> {
+    let mut backend_canisters = Vec::new();
+    let mut frontend_canisters = Vec::new();
+    for canister in plan.core_canisters.iter().chain(&plan.canisters) {
+        let module_hash = match canister_state(agent, canister.canister_id).await? {
+            CanisterState::Installed { module_hash } => Some(hex::encode(module_hash)),
+            _ => None,
+        };
+        let details = CanisterDetails {
+            name: canister.canister_name.clone(),
+            canister_id: canister.canister_id,
+            subnet_id: canister.subnet_id,
+            url: canister_url(provider_url, &canister.canister_id.to_string())?.to_string(),
+            module_hash,
+        };
+        if NNS_FRONTEND
+            .iter()
+            .any(|frontend| frontend.canister_name == canister.canister_name)
+        {
+            frontend_canisters.push(details);
+        } else {
+            backend_canisters.push(details);
+        }
+    }
+    Ok(NnsDetails {
+        ic_commit: plan.ic_commit.clone(),
+        backend_canisters,
+        frontend_canisters,
+        test_accounts: plan.nns_init.test_accounts.clone(),
+        subnets,
+    })
+}
 
+/// Provides the user with a printout detailing what has been installed for them.
+fn print_nns_details(details: &NnsDetails) {
     println!(
         r#"
 
@@ -394,40 +646,19 @@ Frontend canisters:
 {}
 
 "#,
-        NNS_CORE
+        details
+            .backend_canisters
             .iter()
-            .map(|canister| format!("{:20}  {}\n", canister.canister_name, canister.canister_id))
+            .map(|canister| format!("{:20}  {}\n", canister.name, canister.canister_id))
             .collect::<Vec<String>>()
             .join(""),
-        NNS_FRONTEND
+        details
+            .frontend_canisters
             .iter()
-            .map(|canister| format!(
-                "{:20}  {}\n",
-                canister.canister_name,
-                canister_url(canister.canister_id).unwrap_or_default()
-            ))
+            .map(|canister| format!("{:20}  {}\n", canister.name, canister.url))
             .collect::<Vec<String>>()
             .join("")
     );
-    Ok(())
-}
-
-/// Gets a URL, trying repeatedly until it is available.
-#[context("Failed to download after multiple tries: {}", url)]
-pub async fn get_with_retries(url: &Url) -> anyhow::Result<reqwest::Response> {
-    let mut retry_policy = ExponentialBackoff::default();
-
-    loop {
-        match reqwest::get(url.clone()).await {
-            Ok(response) => {
-                return Ok(response);
-            }
-            Err(err) => match retry_policy.next_backoff() {
-                Some(duration) => tokio::time::sleep(duration).await,
-                None => bail!(err),
-            },
-        }
-    }
 }
 
 /// Gets the local replica type from dfx.json
@@ -493,130 +724,202 @@ pub fn verify_local_replica_type_is_system(
     }
 }
 
-/// Downloads a file
-#[context("Failed to download '{:?}' to '{:?}'.", source, target)]
-pub async fn download(source: &Url, target: &Path) -> anyhow::Result<()> {
-    if target.exists() {
-        println!("Already downloaded: {}", target.to_string_lossy());
-        return Ok(());
-    }
-    println!(
-        "Downloading {}\n  from: {}",
-        target.to_string_lossy(),
-        source.as_str()
-    );
-    let buffer = reqwest::get(source.clone())
-        .await
-        .with_context(|| "Failed to connect")?
-        .bytes()
-        .await
-        .with_context(|| "Download was interrupted")?;
-    let target_parent = target
-        .parent()
-        .unwrap_or_else(|| Path::new(Component::CurDir.as_os_str()));
-    let tmp_dir = tempfile::TempDir::new_in(target_parent)
-        .with_context(|| "Failed to create temporary directory for download")?;
-    let downloaded_filename = {
-        let filename = tmp_dir.path().join("wasm");
-        let mut file = fs::File::create(&filename)
-            .with_context(|| format!("Failed to create temp file at '{}'", filename.display()))?;
-        file.write_all(&buffer)
-            .with_context(|| format!("Failed to write temp file at '{}'.", filename.display()))?;
-        filename
+/// Installs a canister that comes after the core canisters, unless it already runs the planned wasm.
+///
+/// A canister that runs a different wasm is skipped with a warning, unless `force_reinstall` is set.
+async fn install_planned_canister(
+    logger: &Logger,
+    network: &NetworkDescriptor,
+    agent: &Agent,
+    wasm_dir: &Path,
+    canister: &PlannedCanister,
+    force_reinstall: bool,
+) -> anyhow::Result<()> {
+    let PlannedCanister {
+        canister_name,
+        canister_id,
+        wasm_name,
+        init_arg,
+        ..
+    } = canister;
+    let local_wasm_path = wasm_dir.join(wasm_name);
+    let install_mode = match canister_state(agent, *canister_id).await? {
+        CanisterState::Missing => {
+            let created_canister_id = create_canister(agent, *canister_id).await?;
+            if *canister_id != created_canister_id {
+                bail!("Canister '{canister_name}' was installed at an incorrect canister ID.  Expected '{canister_id}' but got '{created_canister_id}'.");
+            }
+            CanisterInstallMode::Install
+        }
+        CanisterState::Empty => CanisterInstallMode::Install,
+        _ if force_reinstall => CanisterInstallMode::Reinstall,
+        state if state.runs(&file_sha256(&local_wasm_path)?) => {
+            info!(
+                logger,
+                "Already installed: {canister_name} at {canister_id}";
+                "canister" => canister_name,
+                "canister_id" => %canister_id
+            );
+            return Ok(());
+        }
+        _ => {
+            warn!(
+                logger,
+                "{canister_name} at {canister_id} runs a different wasm than '{}'.  Skipping it; pass `--force-reinstall {canister_name}` to replace it.",
+                local_wasm_path.display();
+                "canister" => canister_name,
+                "canister_id" => %canister_id
+            );
+            return Ok(());
+        }
     };
-    fs::rename(&downloaded_filename, target).with_context(|| {
-        format!(
-            "Failed to rename '{}' to '{}'",
-            downloaded_filename.display(),
-            target.display()
-        )
-    })?;
-    Ok(())
-}
-
-/// Arguments for the ic-nns-init command line function.
-pub struct IcNnsInitOpts {
-    /// An URL to access one or more NNS subnet replicas.
-    nns_url: String,
-    /// A directory that needs to be populated will all required wasms before calling ic-nns-init.
-    wasm_dir: PathBuf,
-    /// The ID of a test account that ic-nns-init will create and to initialise with tokens.
-    /// Note: At present only one test account is supported.
-    test_accounts: Vec<String>,
-    /// A subnet for SNS canisters.
-    /// Note: In this context we support at most one subnet.
-    sns_subnets: Option<String>,
-    /// A file storing the registry content.
-    local_registry_file: Option<PathBuf>,
+    install_canister(
+        logger,
+        network,
+        agent,
+        canister_name,
+        &local_wasm_path,
+        *canister_id,
+        init_arg.as_deref(),
+        install_mode,
+    )
+    .await
 }
 
-/// Calls the `ic-nns-init` executable.
+/// Authorizes the CMC to create canisters in the given subnets by default, by a proposal of the test neuron.
 ///
-/// Notes:
-///   - Set DFX_IC_NNS_INIT_PATH=<path to binary> to use a different binary for local development
-///   - This won't work with an HSM, because the agent holds a session open
-///   - The provider_url is what the agent connects to, and forwards to the replica.
-#[context("Failed to install NNS components.")]
-pub async fn ic_nns_init(opts: &IcNnsInitOpts, dfx_cache_path: &Path) -> anyhow::Result<()> {
-    let mut args: Vec<OsString> = vec![
-        "--pass-specified-id".into(),
-        "--url".into(),
-        opts.nns_url.clone().into(),
-        "--wasm-dir".into(),
-        opts.wasm_dir.as_os_str().into(),
-    ];
-    if let Some(local_registry_file) = &opts.local_registry_file {
-        args.push("--initial-registry".into());
-        args.push(local_registry_file.into());
-    }
-    for account in &opts.test_accounts {
-        args.push("--initialize-ledger-with-test-accounts".into());
-        args.push(account.into());
-    }
-    if let Some(subnets) = &opts.sns_subnets {
-        args.push("--sns-subnet".into());
-        args.push(subnets.into());
-    }
-    call_extension_bundled_binary("ic-nns-init", &args, dfx_cache_path)
+/// Returns once the proposal has been executed.
+#[context("Failed to authorize subnets for use by the cycles management canister. The CMC may not be able to create canisters.")]
+pub async fn set_cmc_authorized_subnets(
+    logger: &Logger,
+    provider_url: &Url,
+    subnets: &[Principal],
+) -> anyhow::Result<()> {
+    let agent = test_neuron_agent(provider_url).await?;
+    let proposal_id = execute_nns_function(
+        &agent,
+        SET_CMC_AUTHORIZED_SUBNETS_TITLE,
+        &set_cmc_authorized_subnets_summary(subnets),
+        NnsFunction::SetAuthorizedSubnetworks,
+        Encode!(&SetAuthorizedSubnetworkListArgs {
+            who: None,
+            subnets: subnets.to_vec(),
+        })?,
+    )
+    .await?;
+    info!(
+        logger,
+        "Proposal {proposal_id} authorized the cycles minting canister to create canisters on the application subnets.";
+        "proposal_id" => proposal_id
+    );
+    Ok(())
 }
 
-/// Sets the subnets the CMC is authorized to create canisters in.
-#[context("Failed to authorize a subnet for use by the cycles management canister. The CMC may not be able to create canisters.")]
-pub fn set_cmc_authorized_subnets(
-    nns_url: &Url,
-    subnet: &str,
-    dfx_cache_path: &Path,
+/// Sets the ICP/XDR conversion rate of the CMC, by a proposal of the test neuron.
+///
+/// Returns once the proposal has been executed.
+#[context("Failed to set the ICP/XDR conversion rate of the cycles minting canister.")]
+pub async fn set_icp_xdr_conversion_rate(
+    logger: &Logger,
+    provider_url: &Url,
+    xdr_permyriad_per_icp: u64,
 ) -> anyhow::Result<()> {
-    let summary = format!(
-        "Authorize the Cycles Minting Canister to create canisters in the subnet '{}'.",
-        subnet
+    let agent = test_neuron_agent(provider_url).await?;
+    // The CMC only accepts rates that are newer than the one it has.
+    let timestamp_seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .with_context(|| "The system clock is set before 1970")?
+        .as_secs();
+    let proposal_id = execute_nns_function(
+        &agent,
+        SET_ICP_XDR_CONVERSION_RATE_TITLE,
+        &set_icp_xdr_conversion_rate_summary(xdr_permyriad_per_icp),
+        NnsFunction::IcpXdrConversionRate,
+        Encode!(&UpdateIcpXdrConversionRatePayload {
+            data_source: "dfx nns install".to_string(),
+            timestamp_seconds,
+            xdr_permyriad_per_icp,
+            reason: Some(UpdateIcpXdrConversionRatePayloadReason::OldRate),
+        })?,
+    )
+    .await?;
+    info!(
+        logger,
+        "Proposal {proposal_id} set the ICP/XDR conversion rate of the cycles minting canister.";
+        "proposal_id" => proposal_id
     );
-    let args = vec![
-        "--nns-url",
-        nns_url.as_str(),
-        "propose-to-set-authorized-subnetworks",
-        "--test-neuron-proposer",
-        "--proposal-title",
-        "Set Cycles Minting Canister Authorized Subnets",
-        "--summary",
-        &summary,
-        "--subnets",
-        subnet,
-    ];
-    call_extension_bundled_binary("ic-admin", args, dfx_cache_path)
-        .map_err(|e| anyhow!("Call to propose to set authorized subnets failed: {e}"))
+    Ok(())
+}
+
+/// The title of the proposal that sets the ICP/XDR conversion rate of the CMC.
+pub const SET_ICP_XDR_CONVERSION_RATE_TITLE: &str = "Set the ICP/XDR Conversion Rate";
+
+/// The summary of the proposal that sets the ICP/XDR conversion rate of the CMC.
+pub fn set_icp_xdr_conversion_rate_summary(xdr_permyriad_per_icp: u64) -> String {
+    format!(
+        "Set the ICP/XDR conversion rate of the Cycles Minting Canister to {} XDR per ICP.",
+        Decimal::new(xdr_permyriad_per_icp as i64, 4).normalize()
+    )
+}
+
+/// The title of the proposal that authorizes the CMC to create canisters in subnets.
+pub const SET_CMC_AUTHORIZED_SUBNETS_TITLE: &str = "Set Cycles Minting Canister Authorized Subnets";
+
+/// The summary of the proposal that authorizes the CMC to create canisters in subnets.
+pub fn set_cmc_authorized_subnets_summary(subnets: &[Principal]) -> String {
+    format!(
+        "Authorize the Cycles Minting Canister to create canisters in the subnets {}.",
+        subnets
+            .iter()
+            .map(|subnet| format!("'{subnet}'"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// Arguments for the `get_wasm` method of the nns-sns-wasm canister.
+#[derive(CandidType)]
+struct GetWasmRequest {
+    /// The SHA-256 hash of the wasm.
+    hash: Vec<u8>,
+}
+
+/// Response of the `get_wasm` method of the nns-sns-wasm canister.
+#[derive(Deserialize)]
+struct GetWasmResponse {
+    /// The wasm, if it has been uploaded.  Its content is not needed here.
+    wasm: Option<IgnoredAny>,
 }
 
 /// Uploads wasms to the nns-sns-wasm canister.
+///
+/// Wasms that the nns-sns-wasm canister already has are skipped.
 #[context("Failed to upload wasm files to the nns-sns-wasm canister; it may not be possible to create an SNS.")]
-pub fn upload_nns_sns_wasms_canister_wasms(dfx_cache_path: &Path) -> anyhow::Result<()> {
+pub async fn upload_nns_sns_wasms_canister_wasms(
+    logger: &Logger,
+    agent: &Agent,
+    wasm_dir: &Path,
+) -> anyhow::Result<()> {
+    let sns_wasm_canister_id = Principal::from_text(NNS_SNS_WASM.canister_id)?;
     for SnsCanisterInstallation {
         upload_name,
         wasm_name,
         ..
     } in SNS_CANISTERS
     {
-        let wasm_path = nns_wasm_dir(dfx_cache_path).join(wasm_name);
+        let wasm_path = wasm_dir.join(wasm_name);
+        let request = GetWasmRequest {
+            hash: file_sha256(&wasm_path)?,
+        };
+        let response = agent
+            .query(&sns_wasm_canister_id, "get_wasm")
+            .with_arg(Encode!(&request)?)
+            .call()
+            .await?;
+        if Decode!(&response, GetWasmResponse)?.wasm.is_some() {
+            info!(logger, "Already uploaded: {}", wasm_path.display(); "wasm" => wasm_name);
+            continue;
+        }
         add_sns_wasm_for_tests(AddSnsWasmForTestsArgs {
             wasm_file: wasm_path.clone(),
             canister_type: upload_name.to_string(),
@@ -627,37 +930,46 @@ pub fn upload_nns_sns_wasms_canister_wasms(dfx_cache_path: &Path) -> anyhow::Res
     Ok(())
 }
 
-/// Installs a canister without adding it to `dfx.json` or `canister_ids.json`.
+/// Creates an empty canister with the given ID.
 ///
 /// # Errors
 /// - Returns an error if the canister could not be created.
+#[context("Failed to create canister '{specified_id}'.")]
+pub async fn create_canister(agent: &Agent, specified_id: Principal) -> anyhow::Result<Principal> {
+    let mgr = ManagementCanister::create(agent);
+    let builder = mgr
+        .create_canister()
+        .as_provisional_create_with_specified_id(specified_id);
+
+    let res = builder.call_and_wait().await;
+    Ok(res.context("Canister creation call failed.")?.0)
+}
+
+/// Installs a wasm in an existing canister without adding it to `dfx.json` or `canister_ids.json`.
+///
+/// # Errors
+/// - Returns an error if the wasm could not be installed.
 /// # Panics
 /// None
 //
 // Notes:
-// - This does not pass any initialisation argument.  If needed, one can be added to the code.
 // - This function may be needed by other plugins as well.
 #[context("Failed to install canister '{canister_name}' on network '{}' using wasm at '{}'.", network_descriptor.name, wasm_path.display())]
 pub async fn install_canister(
+    logger: &Logger,
     network_descriptor: &NetworkDescriptor,
     agent: &Agent,
     canister_name: &str,
     wasm_path: &Path,
-    specified_id: Principal,
+    canister_id: Principal,
     init_arg: Option<&[u8]>,
-) -> anyhow::Result<Principal> {
-    let mgr = ManagementCanister::create(agent);
-    let builder = mgr
-        .create_canister()
-        .as_provisional_create_with_specified_id(specified_id);
-
-    let res = builder.call_and_wait().await;
-    let canister_id: Principal = res.context("Canister creation call failed.")?.0;
-    let canister_id_str = canister_id.to_text();
-
+    install_mode: CanisterInstallMode,
+) -> anyhow::Result<()> {
+    let logger = logger
+        .new(o!("canister" => canister_name.to_string(), "canister_id" => canister_id.to_string()));
+    let step = Step::start(&logger, format!("Installing {canister_name}"));
     let unit_args = Encode!(&())?;
     let install_args = init_arg.unwrap_or(&unit_args);
-    let install_mode = CanisterInstallMode::Install;
     let call_sender = CallSender::SelectedId;
     fn ask_for_consent(_: &str) -> Result<(), UserConsent> {
         Ok(())
@@ -675,7 +987,7 @@ pub async fn install_canister(
     )
     .await?;
 
-    println!("Installed {canister_name} at {canister_id_str}");
+    step.finish();
 
-    Ok(canister_id)
+    Ok(())
 }
@@ -0,0 +1,1015 @@
+//! Computes everything `dfx nns install` is going to do before anything is changed.
+//!
+//! The same plan is used to perform the installation and, with `--dry-run`, to show it to the user.
+#![warn(missing_docs)]
+#![warn(clippy::missing_docs_in_private_items)]
+
+use crate::install_config::InstallConfig;
+use crate::install_nns::{
+    browser_url, canister_url, origin, set_cmc_authorized_subnets_summary,
+    set_icp_xdr_conversion_rate_summary, BtcNetwork, CanisterArguments, CkBtcMinterArgs,
+    CkBtcMinterInitArgs, CkBtcMinterMode, CyclesLedgerArgs, CyclesLedgerInitArgs, SchemaLabel,
+    SET_CMC_AUTHORIZED_SUBNETS_TITLE, SET_ICP_XDR_CONVERSION_RATE_TITLE,
+};
+use crate::nns_init::{core_init_arg, NnsInitOpts};
+use crate::test_tokens::{default_test_token, TestToken};
+use dfx_extensions_utils::dependencies::download_wasms::nns::{
+    CKBTC, CKBTC_INDEX, CKBTC_LEDGER, CKBTC_MINTER, CKETH_INDEX_MAINNET_ID,
+    CKETH_LEDGER_MAINNET_ID, CYCLES_LEDGER, ICP_INDEX, ICRC1_INDEX, ICRC1_LEDGER,
+    INTERNET_IDENTITY, NNS_DAPP, NNS_IDENTITY, NNS_LEDGER, SNS_AGGREGATOR,
+};
+use dfx_extensions_utils::{
+    ic_repo_wasm_url, sha256_hex, IcNnsInitCanister, SnsCanisterInstallation, StandardCanister,
+    NNS_CORE, NNS_CORE_MANUAL, NNS_FRONTEND, SNS_CANISTERS,
+};
+
+use anyhow::{bail, Context};
+use candid::{Encode, IDLArgs};
+use clap::ValueEnum;
+use ic_agent::export::Principal;
+use ic_icp_index::{IndexArg as IcpIndexArg, InitArg as IcpInitArg};
+use ic_icrc1_index_ng::{IndexArg, InitArg as IndexInitArg};
+use ic_icrc1_ledger::{InitArgsBuilder, LedgerArgument};
+use ic_nervous_system_common_test_keys::TEST_NEURON_1_ID;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The name with which `--skip` and `--only` choose whether the SNS wasms are uploaded to the nns-sns-wasm canister.
+pub const SNS_WASMS: &str = "sns-wasms";
+
+/// Which NNS components `dfx nns install` deploys.
+///
+/// The core canisters are initialized together, so every profile includes all of them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum InstallProfile {
+    /// Only the core NNS canisters, such as the ledger, governance, root and the cycles minting canister.
+    Minimal,
+    /// The core NNS canisters, the SNS wasms and the ICRC-1 ledger, ICRC-1 index and ICP index canisters.
+    Core,
+    /// Everything, including Internet Identity, the NNS dapp and the SNS aggregator.
+    #[default]
+    Full,
+}
+
+impl InstallProfile {
+    /// Whether the profile includes a canister that is installed after the core canisters, or the SNS wasms.
+    ///
+    /// Optional components are installed on request with any profile.
+    fn includes(self, canister_name: &str) -> bool {
+        let in_core_profile = canister_name == SNS_WASMS
+            || NNS_CORE_MANUAL
+                .iter()
+                .any(|canister| canister.canister_name == canister_name);
+        let is_frontend = NNS_FRONTEND
+            .iter()
+            .any(|canister| canister.canister_name == canister_name);
+        match self {
+            InstallProfile::Minimal => !in_core_profile && !is_frontend,
+            InstallProfile::Core => !is_frontend,
+            InstallProfile::Full => true,
+        }
+    }
+}
+
+/// The subnets that `dfx nns install` uses.
+///
+/// Canisters are placed where mainnet puts them if the local network has the matching subnet, and on the NNS
+/// subnet otherwise.
+#[derive(Serialize)]
+pub struct Subnets {
+    /// The NNS subnet, where the NNS canisters are installed.
+    pub nns: Principal,
+    /// The subnet that hosts Internet Identity and the cycles ledger on mainnet, if the local network has one.
+    pub ii: Option<Principal>,
+    /// The subnet that hosts the ckBTC and ckETH canisters on mainnet, if the local network has one.
+    pub fiduciary: Option<Principal>,
+    /// The subnet in which the nns-sns-wasm canister creates SNS canisters.
+    pub sns: Principal,
+    /// The subnets the cycles minting canister creates canisters in by default.
+    pub app: Vec<Principal>,
+}
+
+impl Subnets {
+    /// The subnet Internet Identity and the cycles ledger are installed on.
+    pub fn ii_subnet(&self) -> Principal {
+        self.ii.unwrap_or(self.nns)
+    }
+
+    /// The subnet the ckBTC canisters and the default test token are installed on.
+    pub fn fiduciary_subnet(&self) -> Principal {
+        self.fiduciary.unwrap_or(self.nns)
+    }
+
+    /// The ID of Internet Identity.
+    ///
+    /// On an II subnet, Internet Identity is installed at its mainnet ID, which is otherwise taken by the empty
+    /// `nns-identity` canister.
+    pub fn internet_identity_id(&self) -> anyhow::Result<Principal> {
+        let canister_id = if self.ii.is_some() {
+            NNS_IDENTITY.canister_id
+        } else {
+            INTERNET_IDENTITY.canister_id
+        };
+        Ok(Principal::from_text(canister_id)?)
+    }
+
+    /// The ID of the ledger or index of the default test token, given its ID in `NNS_CORE_MANUAL`.
+    ///
+    /// On a fiduciary subnet, the default test token is installed at the mainnet ckETH IDs.
+    pub fn default_test_token_id(&self, canister_id: &str) -> anyhow::Result<Principal> {
+        let canister_id = match self.fiduciary {
+            Some(_) if canister_id == ICRC1_LEDGER.canister_id => CKETH_LEDGER_MAINNET_ID,
+            Some(_) if canister_id == ICRC1_INDEX.canister_id => CKETH_INDEX_MAINNET_ID,
+            _ => canister_id,
+        };
+        Ok(Principal::from_text(canister_id)?)
+    }
+}
+
+/// A canister that `dfx nns install` will deploy.
+pub struct PlannedCanister {
+    /// The name of the canister as typically entered in dfx.json or used in `dfx canister id NAME`.
+    pub canister_name: String,
+    /// The ID the canister is installed at.
+    pub canister_id: Principal,
+    /// The subnet the canister is installed on.
+    pub subnet_id: Principal,
+    /// The basename of the wasm file that is installed.
+    pub wasm_name: String,
+    /// The URL from which the wasm file is downloaded.
+    pub wasm_url: Url,
+    /// The pinned SHA-256 hash of the downloaded file.
+    ///
+    /// `None` for wasms from the IC repository, which are verified against the hashes published for the IC commit,
+    /// and for wasms without a pinned hash, which are only downloaded if `DFX_ALLOW_UNVERIFIED_DOWNLOADS` is set.
+    pub wasm_sha256: Option<&'static str>,
+    /// The init argument, if any.  Core canisters without an init argument are created empty.
+    pub init_arg: Option<Vec<u8>>,
+    /// The canisters that have to be installed first, e.g. the ledger of an index canister.
+    pub dependencies: Vec<Principal>,
+}
+
+/// Everything `dfx nns install` is going to do, in order.
+pub struct InstallPlan {
+    /// The IC commit the NNS wasms are taken from.
+    pub ic_commit: String,
+    /// The directory the wasms are downloaded to and installed from.
+    pub wasm_dir: PathBuf,
+    /// Settings for the canisters in `NNS_CORE`.
+    pub nns_init: NnsInitOpts,
+    /// The core canisters, which are installed together.
+    pub core_canisters: Vec<PlannedCanister>,
+    /// The canisters installed after the core canisters, several at a time; see `install_waves`.
+    pub canisters: Vec<PlannedCanister>,
+    /// Whether the SNS wasms are uploaded to the nns-sns-wasm canister.
+    pub upload_sns_wasms: bool,
+    /// The URL used to submit NNS proposals.
+    pub nns_url: Url,
+    /// The subnets the cycles minting canister is authorized to create canisters in.
+    pub cmc_subnet_ids: Vec<Principal>,
+    /// The ICP/XDR conversion rate to set in the cycles minting canister, in ten-thousandths of an XDR per ICP.
+    pub icp_xdr_permyriad: Option<u64>,
+}
+
+impl InstallPlan {
+    /// Creates the plan for installing the NNS canisters on the NNS subnet.
+    ///
+    /// NNS proposals are submitted through the provider URL.
+    pub fn new(
+        wasm_dir: PathBuf,
+        nns_init: NnsInitOpts,
+        provider_url: &Url,
+        subnets: &Subnets,
+        config: &InstallConfig,
+    ) -> anyhow::Result<Self> {
+        let ic_commit = &config.ic_commit();
+        let nns_subnet_id = subnets.nns;
+        let payloads = nns_init.init_payloads()?;
+        let core_canisters = NNS_CORE
+            .iter()
+            // On an II subnet, Internet Identity takes the ID of the placeholder.
+            .filter(|canister| {
+                subnets.ii.is_none() || canister.canister_id != NNS_IDENTITY.canister_id
+            })
+            .map(
+                |IcNnsInitCanister {
+                     canister_name,
+                     wasm_name,
+                     test_wasm_name,
+                     canister_id,
+                 }| {
+                    // The test wasm is installed, if there is one.
+                    let wasm_name = test_wasm_name.unwrap_or(wasm_name);
+                    Ok(PlannedCanister {
+                        canister_name: canister_name.to_string(),
+                        canister_id: Principal::from_text(canister_id)?,
+                        subnet_id: nns_subnet_id,
+                        wasm_name: wasm_name.to_string(),
+                        wasm_url: ic_repo_wasm_url(wasm_name, ic_commit)?,
+                        wasm_sha256: None,
+                        init_arg: core_init_arg(canister_name, canister_id, &payloads)?,
+                        dependencies: Vec::new(),
+                    })
+                },
+            )
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut canisters = Vec::new();
+        for IcNnsInitCanister {
+            canister_name,
+            wasm_name,
+            canister_id,
+            ..
+        } in NNS_CORE_MANUAL
+        {
+            let is_default_test_token =
+                [ICRC1_LEDGER.canister_id, ICRC1_INDEX.canister_id].contains(canister_id);
+            if is_default_test_token && !config.test_tokens.is_empty() {
+                continue;
+            }
+            let (planned_id, subnet_id) = if is_default_test_token {
+                (
+                    subnets.default_test_token_id(canister_id)?,
+                    subnets.fiduciary_subnet(),
+                )
+            } else {
+                (Principal::from_text(canister_id)?, nns_subnet_id)
+            };
+            canisters.push(PlannedCanister {
+                canister_name: canister_name.to_string(),
+                canister_id: planned_id,
+                subnet_id,
+                wasm_name: wasm_name.to_string(),
+                wasm_url: ic_repo_wasm_url(wasm_name, ic_commit)?,
+                wasm_sha256: None,
+                init_arg: core_manual_init_arg(canister_id, subnets)?,
+                dependencies: core_manual_dependencies(canister_id, subnets)?,
+            });
+        }
+        for canister in NNS_FRONTEND {
+            let mut planned = standard_canister(
+                canister,
+                nns_subnet_id,
+                frontend_init_arg(canister.canister_id, provider_url, subnets, config)?,
+            )?;
+            if canister.canister_id == INTERNET_IDENTITY.canister_id {
+                planned.canister_id = subnets.internet_identity_id()?;
+                planned.subnet_id = subnets.ii_subnet();
+            }
+            if let Some(wasm_url) = config.frontend_wasm_url(canister.canister_name)? {
+                // Cache the wasm under a name derived from the URL, so that changing the URL downloads the new wasm.
+                planned.wasm_name = format!(
+                    "{}-{}",
+                    &sha256_hex(wasm_url.as_str().as_bytes())[..8],
+                    canister.wasm_name
+                );
+                planned.wasm_url = wasm_url;
+                planned.wasm_sha256 = None;
+            }
+            canisters.push(planned);
+        }
+        if config.with_cycles_ledger {
+            canisters.push(standard_canister(
+                &CYCLES_LEDGER,
+                subnets.ii_subnet(),
+                Some(cycles_ledger_init_arg()?),
+            )?);
+        }
+        if let Some(btc_network) = config.ckbtc() {
+            for IcNnsInitCanister {
+                canister_name,
+                wasm_name,
+                canister_id,
+                ..
+            } in CKBTC
+            {
+                canisters.push(PlannedCanister {
+                    canister_name: canister_name.to_string(),
+                    canister_id: Principal::from_text(canister_id)?,
+                    subnet_id: subnets.fiduciary_subnet(),
+                    wasm_name: wasm_name.to_string(),
+                    wasm_url: ic_repo_wasm_url(wasm_name, ic_commit)?,
+                    wasm_sha256: None,
+                    init_arg: Some(ckbtc_init_arg(canister_id, btc_network)?),
+                    dependencies: if *canister_id == CKBTC_INDEX.canister_id {
+                        vec![Principal::from_text(CKBTC_LEDGER.canister_id)?]
+                    } else {
+                        Vec::new()
+                    },
+                });
+            }
+        }
+        for (n, token) in config.test_tokens.iter().enumerate() {
+            canisters.push(PlannedCanister {
+                canister_name: token.ledger_canister_name(),
+                canister_id: TestToken::ledger_id(n),
+                subnet_id: nns_subnet_id,
+                wasm_name: ICRC1_LEDGER.wasm_name.to_string(),
+                wasm_url: ic_repo_wasm_url(ICRC1_LEDGER.wasm_name, ic_commit)?,
+                wasm_sha256: None,
+                init_arg: Some(token.ledger_init_arg()?),
+                dependencies: Vec::new(),
+            });
+            if token.index {
+                canisters.push(PlannedCanister {
+                    canister_name: token.index_canister_name(),
+                    canister_id: TestToken::index_id(n),
+                    subnet_id: nns_subnet_id,
+                    wasm_name: ICRC1_INDEX.wasm_name.to_string(),
+                    wasm_url: ic_repo_wasm_url(ICRC1_INDEX.wasm_name, ic_commit)?,
+                    wasm_sha256: None,
+                    init_arg: Some(TestToken::index_init_arg(n)?),
+                    dependencies: vec![TestToken::ledger_id(n)],
+                });
+            }
+        }
+
+        Ok(Self {
+            ic_commit: ic_commit.clone(),
+            wasm_dir,
+            nns_init,
+            core_canisters,
+            canisters,
+            upload_sns_wasms: true,
+            nns_url: provider_url.clone(),
+            cmc_subnet_ids: subnets.app.clone(),
+            icp_xdr_permyriad: config.icp_xdr_permyriad()?,
+        })
+    }
+
+    /// Restricts the canisters installed after the core canisters, and the upload of the SNS wasms, to those chosen on
+    /// the command line.
+    ///
+    /// With `only`, exactly those canisters are installed, and the SNS wasms are only uploaded if `only` includes
+    /// `sns-wasms`.  Otherwise the components in the profile are installed, except those in `skip`.
+    ///
+    /// # Errors
+    /// - Returns an error if a name is not one of the components that can be chosen.
+    pub fn select_canisters(
+        &mut self,
+        profile: InstallProfile,
+        skip: &[String],
+        only: &[String],
+    ) -> anyhow::Result<()> {
+        for canister_name in skip.iter().chain(only) {
+            if self
+                .core_canisters
+                .iter()
+                .any(|canister| &canister.canister_name == canister_name)
+            {
+                bail!("'{canister_name}' is installed together with the other core canisters and cannot be chosen individually.");
+            }
+            if canister_name != SNS_WASMS && !self.includes(canister_name) {
+                bail!(
+                    "Unknown canister '{canister_name}'.  Choose from: {}",
+                    self.canisters
+                        .iter()
+                        .map(|canister| canister.canister_name.as_str())
+                        .chain([SNS_WASMS])
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+        }
+        let selected = |canister_name: &str| {
+            if only.is_empty() {
+                profile.includes(canister_name)
+                    && !skip.iter().any(|skipped| skipped == canister_name)
+            } else {
+                only.iter().any(|chosen| chosen == canister_name)
+            }
+        };
+        self.canisters
+            .retain(|canister| selected(&canister.canister_name));
+        self.upload_sns_wasms = selected(SNS_WASMS);
+        Ok(())
+    }
+
+    /// Whether a canister is installed after the core canisters.
+    pub fn includes(&self, canister_name: &str) -> bool {
+        self.canisters
+            .iter()
+            .any(|canister| canister.canister_name == canister_name)
+    }
+
+    /// Groups the canisters installed after the core canisters into waves.
+    ///
+    /// The canisters of a wave do not depend on each other, so they can be installed concurrently, and every wave
+    /// comes after the canisters it depends on.  Dependencies outside the plan, such as the NNS ledger, are installed
+    /// with the core canisters.
+    ///
+    /// # Errors
+    /// - Returns an error if canisters depend on each other in a cycle.
+    pub fn install_waves(&self) -> anyhow::Result<Vec<Vec<&PlannedCanister>>> {
+        let mut pending: Vec<&PlannedCanister> = self.canisters.iter().collect();
+        let mut waves = Vec::new();
+        while !pending.is_empty() {
+            let (wave, blocked): (Vec<&PlannedCanister>, Vec<&PlannedCanister>) =
+                pending.iter().copied().partition(|canister| {
+                    canister.dependencies.iter().all(|dependency| {
+                        !pending.iter().any(|other| other.canister_id == *dependency)
+                    })
+                });
+            if wave.is_empty() {
+                bail!(
+                    "The canisters {} depend on each other.",
+                    blocked
+                        .iter()
+                        .map(|canister| canister.canister_name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+            waves.push(wave);
+            pending = blocked;
+        }
+        Ok(waves)
+    }
+
+    /// The basenames of all the wasm files the installation needs.
+    pub fn wasm_names(&self) -> Vec<&str> {
+        // Core canisters without an init argument are created empty, so their wasms are not needed.
+        let mut wasm_names: Vec<&str> = self
+            .core_canisters
+            .iter()
+            .filter(|canister| canister.init_arg.is_some())
+            .map(|canister| canister.wasm_name.as_str())
+            .collect();
+        if self.upload_sns_wasms {
+            wasm_names.extend(SNS_CANISTERS.iter().map(|canister| canister.wasm_name));
+        }
+        for canister in &self.canisters {
+            if !wasm_names.contains(&canister.wasm_name.as_str()) {
+                wasm_names.push(&canister.wasm_name);
+            }
+        }
+        wasm_names
+    }
+
+    /// Prints the plan in a form that can be reviewed before running the installation.
+    pub fn print(&self) {
+        let canister_details = |canister: &PlannedCanister, no_init_arg: &str| -> String {
+            let wasm_path = self.wasm_dir.join(&canister.wasm_name);
+            let cache_status = if wasm_path.exists() {
+                "cached"
+            } else {
+                "not yet downloaded"
+            };
+            let init_arg = match &canister.init_arg {
+                Some(arg) => decode_init_arg(arg),
+                None => no_init_arg.to_string(),
+            };
+            let after = if canister.dependencies.is_empty() {
+                String::new()
+            } else {
+                format!("    after:   {}\n", join_principals(&canister.dependencies))
+            };
+            format!(
+                "  {:20}  {}\n    subnet:  {}\n    wasm:    {} ({cache_status})\n    source:  {}\n    init:    {}\n{after}",
+                canister.canister_name,
+                canister.canister_id,
+                canister.subnet_id,
+                wasm_path.display(),
+                canister.wasm_url,
+                init_arg.replace('\n', "\n             "),
+            )
+        };
+        let sns_wasms = if !self.upload_sns_wasms {
+            "  skipped\n".to_string()
+        } else {
+            SNS_CANISTERS
+                .iter()
+                .map(
+                    |SnsCanisterInstallation {
+                         upload_name,
+                         wasm_name,
+                         ..
+                     }| {
+                        format!(
+                            "  {:20}  {}\n",
+                            upload_name,
+                            self.wasm_dir.join(wasm_name).display()
+                        )
+                    },
+                )
+                .collect::<Vec<String>>()
+                .join("")
+        };
+        let canisters = if self.canisters.is_empty() {
+            "  none\n".to_string()
+        } else {
+            self.canisters
+                .iter()
+                .map(|canister| canister_details(canister, "()"))
+                .collect::<Vec<String>>()
+                .join("")
+        };
+
+        println!(
+            r#"
+#########################
+# NNS INSTALLATION PLAN #
+#########################
+
+IC commit: {}
+Wasm directory: {}
+
+1. Create and install the core NNS canisters:
+  test accounts:     {}
+  SNS subnets:       {}
+  initial registry:  {}
+  neurons:           {}
+
+{}
+2. Upload the SNS wasms to the nns-sns-wasm canister:
+{}
+3. Install the remaining canisters:
+{}
+4. Authorize the cycles minting canister to create canisters on subnets {} with a proposal to {}:
+  proposer:  test neuron {}
+  function:  SetAuthorizedSubnetworks
+  title:     {}
+  summary:   {}
+{}
+No changes have been made.
+"#,
+            self.ic_commit,
+            self.wasm_dir.display(),
+            self.nns_init.test_accounts.join(", "),
+            join_principals(&self.nns_init.sns_subnets),
+            self.nns_init
+                .local_registry_file
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(|| "none".to_string()),
+            if self.nns_init.neurons.is_empty() {
+                "test neurons only".to_string()
+            } else {
+                self.nns_init
+                    .neurons
+                    .iter()
+                    .map(|neuron| format!(
+                        "\n    {}  controller: {}  stake: {}  dissolve delay: {}s",
+                        neuron.id,
+                        neuron.controller,
+                        neuron.stake(),
+                        neuron.dissolve_delay_seconds()
+                    ))
+                    .collect::<Vec<_>>()
+                    .join("")
+            },
+            self.core_canisters
+                .iter()
+                .map(|canister| canister_details(canister, "none; the canister is created empty"))
+                .collect::<Vec<String>>()
+                .join(""),
+            sns_wasms,
+            canisters,
+            join_principals(&self.cmc_subnet_ids),
+            self.nns_url,
+            TEST_NEURON_1_ID,
+            SET_CMC_AUTHORIZED_SUBNETS_TITLE,
+            set_cmc_authorized_subnets_summary(&self.cmc_subnet_ids),
+            match self.icp_xdr_permyriad {
+                Some(xdr_permyriad_per_icp) => format!(
+                    "\n5. Set the ICP/XDR conversion rate of the cycles minting canister with a proposal to {}:\n  proposer:  test neuron {}\n  function:  IcpXdrConversionRate\n  title:     {}\n  summary:   {}\n",
+                    self.nns_url,
+                    TEST_NEURON_1_ID,
+                    SET_ICP_XDR_CONVERSION_RATE_TITLE,
+                    set_icp_xdr_conversion_rate_summary(xdr_permyriad_per_icp),
+                ),
+                None => String::new(),
+            },
+        );
+    }
+}
+
+/// Lists subnets or canisters for the installation plan.
+fn join_principals(principals: &[Principal]) -> String {
+    principals
+        .iter()
+        .map(|principal| principal.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Renders an encoded init argument in candid text format.
+fn decode_init_arg(arg: &[u8]) -> String {
+    IDLArgs::from_bytes(arg)
+        .map(|args| args.to_string())
+        .unwrap_or_else(|err| format!("<could not decode {} bytes: {err}>", arg.len()))
+}
+
+/// The init argument for a canister in `NNS_CORE_MANUAL`.
+fn core_manual_init_arg(canister_id: &str, subnets: &Subnets) -> anyhow::Result<Option<Vec<u8>>> {
+    let arg = if canister_id == ICRC1_LEDGER.canister_id {
+        Some(default_test_token().ledger_init_arg()?)
+    } else if canister_id == ICRC1_INDEX.canister_id {
+        let cketh_index_args = IndexArg::Init(IndexInitArg {
+            ledger_id: subnets.default_test_token_id(ICRC1_LEDGER.canister_id)?,
+            retrieve_blocks_from_ledger_interval_seconds: None,
+        });
+        Some(Encode!(&Some(cketh_index_args))?)
+    } else if canister_id == ICP_INDEX.canister_id {
+        let icp_index_args = IcpIndexArg::Init(IcpInitArg {
+            ledger_id: Principal::from_str(NNS_LEDGER.canister_id)?,
+            retrieve_blocks_from_ledger_interval_seconds: None,
+        });
+        Some(Encode!(&Some(icp_index_args))?)
+    } else {
+        None
+    };
+    Ok(arg)
+}
+
+/// The canisters that a canister in `NNS_CORE_MANUAL` depends on: index canisters need their ledger.
+fn core_manual_dependencies(
+    canister_id: &str,
+    subnets: &Subnets,
+) -> anyhow::Result<Vec<Principal>> {
+    let dependencies = if canister_id == ICRC1_INDEX.canister_id {
+        vec![subnets.default_test_token_id(ICRC1_LEDGER.canister_id)?]
+    } else if canister_id == ICP_INDEX.canister_id {
+        vec![Principal::from_str(NNS_LEDGER.canister_id)?]
+    } else {
+        Vec::new()
+    };
+    Ok(dependencies)
+}
+
+/// Plans a canister whose wasm is downloaded from outside the IC repository.
+fn standard_canister(
+    canister: &StandardCanister,
+    subnet_id: Principal,
+    init_arg: Option<Vec<u8>>,
+) -> anyhow::Result<PlannedCanister> {
+    let StandardCanister {
+        canister_name,
+        canister_id,
+        wasm_name,
+        wasm_url,
+        wasm_sha256,
+    } = canister;
+    Ok(PlannedCanister {
+        canister_name: canister_name.to_string(),
+        canister_id: Principal::from_text(canister_id)?,
+        subnet_id,
+        wasm_name: wasm_name.to_string(),
+        wasm_url: Url::parse(wasm_url)
+            .with_context(|| format!("Could not parse url for {canister_name} wasm: {wasm_url}"))?,
+        wasm_sha256: *wasm_sha256,
+        init_arg,
+        dependencies: Vec::new(),
+    })
+}
+
+/// The init argument for the cycles ledger.
+fn cycles_ledger_init_arg() -> anyhow::Result<Vec<u8>> {
+    Ok(Encode!(&CyclesLedgerArgs::Init(CyclesLedgerInitArgs {
+        max_blocks_per_request: 1_000,
+        index_id: None,
+    }))?)
+}
+
+/// The init argument for a canister in `CKBTC`.
+fn ckbtc_init_arg(canister_id: &str, btc_network: BtcNetwork) -> anyhow::Result<Vec<u8>> {
+    let ledger_id = Principal::from_text(CKBTC_LEDGER.canister_id)?;
+    let minter_id = Principal::from_text(CKBTC_MINTER.canister_id)?;
+    let arg = if canister_id == CKBTC_LEDGER.canister_id {
+        let ckbtc_ledger_args = InitArgsBuilder::for_tests()
+            .with_minting_account(minter_id)
+            .with_transfer_fee(10_u64)
+            .with_token_symbol("ckBTC".to_string())
+            .with_token_name("ckBTC".to_string())
+            .build();
+        Encode!(&(LedgerArgument::Init(ckbtc_ledger_args)))?
+    } else if canister_id == CKBTC_INDEX.canister_id {
+        Encode!(&Some(IndexArg::Init(IndexInitArg {
+            ledger_id,
+            retrieve_blocks_from_ledger_interval_seconds: None,
+        })))?
+    } else {
+        Encode!(&CkBtcMinterArgs::Init(CkBtcMinterInitArgs {
+            btc_network,
+            ledger_id,
+            // The threshold ECDSA key that local replicas provide.
+            ecdsa_key_name: "dfx_test_key".to_string(),
+            retrieve_btc_min_amount: 10_000,
+            max_time_in_queue_nanos: 10_000_000_000,
+            min_confirmations: Some(1),
+            mode: CkBtcMinterMode::GeneralAvailability,
+            check_fee: Some(0),
+            btc_checker_principal: None,
+        }))?
+    };
+    Ok(arg)
+}
+
+/// The init argument for a canister in `NNS_FRONTEND`.
+///
+/// The URLs in the frontend configuration are derived from the provider URL, so any port may be used.
+fn frontend_init_arg(
+    canister_id: &str,
+    provider_url: &Url,
+    subnets: &Subnets,
+    config: &InstallConfig,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let arg = if canister_id == NNS_DAPP.canister_id {
+        let host = origin(&browser_url(provider_url)?);
+        let mut nns_dapp_metadata = vec![
+            ("API_HOST".to_string(), host.clone()),
+            (
+                "CYCLES_MINTING_CANISTER_ID".to_string(),
+                "rkp4c-7iaaa-aaaaa-aaaca-cai".to_string(),
+            ),
+            ("DFX_NETWORK".to_string(), "local".to_string()),
+            (
+                "FEATURE_FLAGS".to_string(),
+                config.nns_dapp_feature_flags()?,
+            ),
+            ("FETCH_ROOT_KEY".to_string(), "true".to_string()),
+            (
+                "GOVERNANCE_CANISTER_ID".to_string(),
+                "rrkah-fqaaa-aaaaa-aaaaq-cai".to_string(),
+            ),
+            ("HOST".to_string(), host.clone()),
+            (
+                "IDENTITY_SERVICE_URL".to_string(),
+                origin(&canister_url(
+                    provider_url,
+                    &subnets.internet_identity_id()?.to_string(),
+                )?),
+            ),
+            (
+                "INDEX_CANISTER_ID".to_string(),
+                ICP_INDEX.canister_id.to_string(),
+            ),
+            (
+                "LEDGER_CANISTER_ID".to_string(),
+                "ryjl3-tyaaa-aaaaa-aaaba-cai".to_string(),
+            ),
+            (
+                "OWN_CANISTER_ID".to_string(),
+                NNS_DAPP.canister_id.to_string(),
+            ),
+            (
+                "ROBOTS".to_string(),
+                "<meta name=\"robots\" content=\"noindex, nofollow\" />".to_string(),
+            ),
+            (
+                "SNS_AGGREGATOR_URL".to_string(),
+                origin(&canister_url(provider_url, SNS_AGGREGATOR.canister_id)?),
+            ),
+            ("STATIC_HOST".to_string(), host),
+            ("TVL_CANISTER_ID".to_string(), "".to_string()),
+            (
+                "WASM_CANISTER_ID".to_string(),
+                "qaa6y-5yaaa-aaaaa-aaafa-cai".to_string(),
+            ),
+        ];
+        if config.test_tokens.is_empty() {
+            let prefix = default_test_token().nns_dapp_key_prefix();
+            nns_dapp_metadata.push((
+                format!("{prefix}_INDEX_CANISTER_ID"),
+                subnets
+                    .default_test_token_id(ICRC1_INDEX.canister_id)?
+                    .to_string(),
+            ));
+            nns_dapp_metadata.push((
+                format!("{prefix}_LEDGER_CANISTER_ID"),
+                subnets
+                    .default_test_token_id(ICRC1_LEDGER.canister_id)?
+                    .to_string(),
+            ));
+        }
+        for (n, token) in config.test_tokens.iter().enumerate() {
+            let prefix = token.nns_dapp_key_prefix();
+            if token.index {
+                nns_dapp_metadata.push((
+                    format!("{prefix}_INDEX_CANISTER_ID"),
+                    TestToken::index_id(n).to_string(),
+                ));
+            }
+            nns_dapp_metadata.push((
+                format!("{prefix}_LEDGER_CANISTER_ID"),
+                TestToken::ledger_id(n).to_string(),
+            ));
+        }
+        if config.with_cycles_ledger {
+            nns_dapp_metadata.push((
+                "CYCLES_LEDGER_CANISTER_ID".to_string(),
+                CYCLES_LEDGER.canister_id.to_string(),
+            ));
+        }
+        if config.ckbtc().is_some() {
+            for (key, canister) in [
+                ("CKBTC_INDEX_CANISTER_ID", &CKBTC_INDEX),
+                ("CKBTC_LEDGER_CANISTER_ID", &CKBTC_LEDGER),
+                ("CKBTC_MINTER_CANISTER_ID", &CKBTC_MINTER),
+            ] {
+                nns_dapp_metadata.push((key.to_string(), canister.canister_id.to_string()));
+            }
+        }
+        nns_dapp_metadata.sort();
+        let nns_dapp_init_args = Some(CanisterArguments {
+            args: nns_dapp_metadata,
+            schema: Some(SchemaLabel::AccountsInStableMemory),
+        });
+        Some(Encode!(&nns_dapp_init_args)?)
+    } else if canister_id == SNS_AGGREGATOR.canister_id {
+        Some(Encode!(&Some(config.sns_aggregator.clone()))?)
+    } else {
+        None
+    };
+    Ok(arg)
+}
+
+#[cfg(test)]
+/// Tests of the pure parts of the plan, which need no network.
+mod tests {
+    use super::*;
+
+    /// A planned canister with a made-up ID, e.g. `Principal::from_slice(&[1])` for `id` 1.
+    fn canister(canister_name: &str, id: u8, dependencies: &[u8]) -> PlannedCanister {
+        PlannedCanister {
+            canister_name: canister_name.to_string(),
+            canister_id: Principal::from_slice(&[id]),
+            subnet_id: Principal::anonymous(),
+            wasm_name: format!("{canister_name}.wasm"),
+            wasm_url: Url::parse("https://example.com/canister.wasm.gz").unwrap(),
+            wasm_sha256: None,
+            init_arg: None,
+            dependencies: dependencies
+                .iter()
+                .map(|id| Principal::from_slice(&[*id]))
+                .collect(),
+        }
+    }
+
+    /// A plan with the NNS ledger as its only core canister.
+    fn plan(canisters: Vec<PlannedCanister>) -> InstallPlan {
+        InstallPlan {
+            ic_commit: "0123456789abcdef0123456789abcdef01234567".to_string(),
+            wasm_dir: PathBuf::from("wasms"),
+            nns_init: NnsInitOpts {
+                test_accounts: Vec::new(),
+                sns_subnets: Vec::new(),
+                local_registry_file: None,
+                neurons: Vec::new(),
+            },
+            core_canisters: vec![canister(NNS_LEDGER.canister_name, 100, &[])],
+            canisters,
+            upload_sns_wasms: true,
+            nns_url: Url::parse("http://localhost:4943").unwrap(),
+            cmc_subnet_ids: Vec::new(),
+            icp_xdr_permyriad: None,
+        }
+    }
+
+    /// A plan with a core manual canister, a frontend canister and an optional canister.
+    fn selection_plan() -> InstallPlan {
+        plan(vec![
+            canister(ICP_INDEX.canister_name, 1, &[]),
+            canister(NNS_DAPP.canister_name, 2, &[]),
+            canister(CYCLES_LEDGER.canister_name, 3, &[]),
+        ])
+    }
+
+    /// The names of the canisters installed after the core canisters.
+    fn names(plan: &InstallPlan) -> Vec<&str> {
+        plan.canisters
+            .iter()
+            .map(|canister| canister.canister_name.as_str())
+            .collect()
+    }
+
+    #[test]
+    /// Each profile installs its own components after the core canisters.
+    fn profiles_select_their_components() {
+        let mut full = selection_plan();
+        full.select_canisters(InstallProfile::Full, &[], &[])
+            .unwrap();
+        assert_eq!(names(&full), ["nns-icp-index", "nns-dapp", "cycles_ledger"]);
+        assert!(full.upload_sns_wasms);
+
+        let mut core = selection_plan();
+        core.select_canisters(InstallProfile::Core, &[], &[])
+            .unwrap();
+        assert_eq!(names(&core), ["nns-icp-index", "cycles_ledger"]);
+        assert!(core.upload_sns_wasms);
+
+        let mut minimal = selection_plan();
+        minimal
+            .select_canisters(InstallProfile::Minimal, &[], &[])
+            .unwrap();
+        assert_eq!(names(&minimal), ["cycles_ledger"]);
+        assert!(!minimal.upload_sns_wasms);
+    }
+
+    #[test]
+    /// `--skip` leaves out canisters and the SNS wasms.
+    fn skip_leaves_out_components() {
+        let mut plan = selection_plan();
+        plan.select_canisters(
+            InstallProfile::Full,
+            &["nns-dapp".to_string(), SNS_WASMS.to_string()],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(names(&plan), ["nns-icp-index", "cycles_ledger"]);
+        assert!(!plan.upload_sns_wasms);
+    }
+
+    #[test]
+    /// `--only` installs exactly the named canisters and uploads the SNS wasms only if named.
+    fn only_selects_exactly_the_named_components() {
+        let mut plan = selection_plan();
+        plan.select_canisters(InstallProfile::Full, &[], &["nns-icp-index".to_string()])
+            .unwrap();
+        assert_eq!(names(&plan), ["nns-icp-index"]);
+        assert!(!plan.upload_sns_wasms);
+
+        let mut plan = selection_plan();
+        plan.select_canisters(InstallProfile::Full, &[], &[SNS_WASMS.to_string()])
+            .unwrap();
+        assert!(names(&plan).is_empty());
+        assert!(plan.upload_sns_wasms);
+    }
+
+    #[test]
+    /// Core canisters and unknown names are rejected.
+    fn core_and_unknown_canisters_cannot_be_chosen() {
+        let err = selection_plan()
+            .select_canisters(InstallProfile::Full, &["nns-ledger".to_string()], &[])
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("'nns-ledger' is installed together with the other core canisters"));
+
+        let err = selection_plan()
+            .select_canisters(InstallProfile::Full, &[], &["nns-foo".to_string()])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown canister 'nns-foo'.  Choose from: nns-icp-index, nns-dapp, cycles_ledger, sns-wasms"
+        );
+    }
+
+    /// The names of the canisters in each wave.
+    fn wave_names<'a>(waves: &[Vec<&'a PlannedCanister>]) -> Vec<Vec<&'a str>> {
+        waves
+            .iter()
+            .map(|wave| {
+                wave.iter()
+                    .map(|canister| canister.canister_name.as_str())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    /// Independent canisters share the first wave, and each canister comes after its dependencies.
+    fn waves_follow_dependencies() {
+        let plan = plan(vec![
+            canister("index", 2, &[1]),
+            canister("ledger", 1, &[]),
+            canister("frontend", 3, &[]),
+            canister("dashboard", 4, &[2]),
+        ]);
+        assert_eq!(
+            wave_names(&plan.install_waves().unwrap()),
+            [vec!["ledger", "frontend"], vec!["index"], vec!["dashboard"]]
+        );
+    }
+
+    #[test]
+    /// Dependencies outside the plan, such as the core canisters, do not hold a canister back.
+    fn dependencies_outside_the_plan_are_ignored() {
+        let plan = plan(vec![
+            canister("icp-index", 1, &[100]),
+            canister("token-index", 2, &[7]),
+        ]);
+        assert_eq!(
+            wave_names(&plan.install_waves().unwrap()),
+            [vec!["icp-index", "token-index"]]
+        );
+        assert!(self::plan(Vec::new()).install_waves().unwrap().is_empty());
+    }
+
+    #[test]
+    /// Canisters that depend on each other cannot be ordered, and the error names them.
+    fn dependency_cycles_are_rejected() {
+        let plan = plan(vec![
+            canister("ledger", 1, &[]),
+            canister("a", 2, &[3]),
+            canister("b", 3, &[2]),
+        ]);
+        assert_eq!(
+            plan.install_waves().unwrap_err().to_string(),
+            "The canisters a, b depend on each other."
+        );
+    }
+}
@@ -0,0 +1,285 @@
+//! The record of what `dfx nns install` installed, kept in the data directory of the local network.
+//!
+//! Later commands read the record instead of assuming the defaults of `dfx nns install`: which IC commit the wasms
+//! came from, where each canister is and which wasm it should run.
+#![warn(missing_docs)]
+#![warn(clippy::missing_docs_in_private_items)]
+
+use crate::install_nns::{CanisterDetails, NnsDetails};
+use crate::install_plan::InstallPlan;
+
+use anyhow::Context;
+use dfx_core::config::model::network_descriptor::NetworkDescriptor;
+use fn_error_context::context;
+use ic_agent::export::Principal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// The name of the state file in the data directory of the local network.
+pub const INSTALL_STATE_FILE_NAME: &str = "nns-install-state.json";
+
+/// What `dfx nns install` installed on a local network.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstallState {
+    /// The IC commit the NNS wasms were taken from.
+    pub ic_commit: String,
+    /// The installed canisters, core canisters first.
+    pub canisters: Vec<InstalledCanister>,
+    /// The ledger accounts that were funded with test ICP.
+    pub test_accounts: Vec<String>,
+    /// The URLs of the frontend canisters, by canister name.
+    pub frontend_urls: BTreeMap<String, String>,
+}
+
+/// A canister installed by `dfx nns install`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstalledCanister {
+    /// The name of the canister, as used by `dfx nns install`.
+    pub canister_name: String,
+    /// The ID of the canister.
+    pub canister_id: Principal,
+    /// The basename of the wasm file of the canister.
+    pub wasm_name: String,
+    /// The hex encoded SHA-256 hash of the installed wasm module, as read from the network.  `None` for the
+    /// placeholder core canisters, which are created empty.
+    pub wasm_sha256: Option<String>,
+}
+
+impl InstallState {
+    /// Describes what an installation plan installed, by the module hashes read from the network.
+    ///
+    /// Canisters that were already installed are recorded with the module they run, which may differ from the wasm in
+    /// `plan.wasm_dir`, e.g. after `dfx nns upgrade`.  The placeholder core canisters are empty, so they have no hash.
+    pub fn from_details(plan: &InstallPlan, details: &NnsDetails) -> Self {
+        let installed: Vec<&CanisterDetails> = details
+            .backend_canisters
+            .iter()
+            .chain(&details.frontend_canisters)
+            .collect();
+        let canisters = plan
+            .core_canisters
+            .iter()
+            .chain(&plan.canisters)
+            .map(|canister| InstalledCanister {
+                canister_name: canister.canister_name.clone(),
+                canister_id: canister.canister_id,
+                wasm_name: canister.wasm_name.clone(),
+                wasm_sha256: installed
+                    .iter()
+                    .find(|details| details.canister_id == canister.canister_id)
+                    .and_then(|details| details.module_hash.clone()),
+            })
+            .collect();
+        let frontend_urls = details
+            .frontend_canisters
+            .iter()
+            .map(|canister| (canister.name.clone(), canister.url.clone()))
+            .collect();
+        Self {
+            ic_commit: plan.ic_commit.clone(),
+            canisters,
+            test_accounts: plan.nns_init.test_accounts.clone(),
+            frontend_urls,
+        }
+    }
+
+    /// The path of the state file of a network.  Only local networks have one.
+    ///
+    /// # Errors
+    /// - Returns an error if the settings of the local network cannot be read.
+    pub fn path(network: &NetworkDescriptor) -> anyhow::Result<Option<PathBuf>> {
+        let Some(local_server_descriptor) = &network.local_server_descriptor else {
+            return Ok(None);
+        };
+        let mut local_server_descriptor = local_server_descriptor.clone();
+        local_server_descriptor.load_settings_digest()?;
+        Ok(Some(
+            local_server_descriptor
+                .data_dir_by_settings_digest()
+                .join(INSTALL_STATE_FILE_NAME),
+        ))
+    }
+
+    /// Reads the state file of a network, if `dfx nns install` has written one.
+    #[context("Failed to read the NNS install state of network '{}'.", network.name)]
+    pub fn load(network: &NetworkDescriptor) -> anyhow::Result<Option<Self>> {
+        let Some(path) = Self::path(network)? else {
+            return Ok(None);
+        };
+        if !path.exists() {
+            return Ok(None);
+        }
+        let json =
+            fs::read_to_string(&path).with_context(|| format!("Unable to read {:?}", path))?;
+        Ok(Some(serde_json::from_str(&json).with_context(|| {
+            format!("Malformed state file {:?}", path)
+        })?))
+    }
+
+    /// Writes the state file of a local network.
+    #[context("Failed to write the NNS install state of network '{}'.", network.name)]
+    pub fn save(&self, network: &NetworkDescriptor) -> anyhow::Result<()> {
+        let path = Self::path(network)?
+            .with_context(|| "Only local networks have an NNS install state.")?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Unable to create {:?}", parent))?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Unable to write {:?}", path))
+    }
+
+    /// The installed canister with this name, if any.
+    pub fn canister(&self, canister_name: &str) -> Option<&InstalledCanister> {
+        self.canisters
+            .iter()
+            .find(|canister| canister.canister_name == canister_name)
+    }
+
+    /// Records that a canister now runs another wasm, e.g. after `dfx nns upgrade`.
+    pub fn record_wasm(&mut self, canister_name: &str, wasm_sha256: &[u8]) {
+        if let Some(canister) = self
+            .canisters
+            .iter_mut()
+            .find(|canister| canister.canister_name == canister_name)
+        {
+            canister.wasm_sha256 = Some(hex::encode(wasm_sha256));
+        }
+    }
+}
+
+#[cfg(test)]
+/// Tests of what the install state records.
+mod tests {
+    use super::*;
+    use crate::install_plan::{PlannedCanister, Subnets};
+    use crate::nns_init::NnsInitOpts;
+    use reqwest::Url;
+
+    /// A planned canister with a made-up ID; `init_arg` marks a core canister that is installed, not a placeholder.
+    fn canister(canister_name: &str, id: u8, init_arg: bool) -> PlannedCanister {
+        PlannedCanister {
+            canister_name: canister_name.to_string(),
+            canister_id: Principal::from_slice(&[id]),
+            subnet_id: Principal::anonymous(),
+            wasm_name: format!("{canister_name}.wasm"),
+            wasm_url: Url::parse("https://example.com/canister.wasm.gz").unwrap(),
+            wasm_sha256: None,
+            init_arg: init_arg.then(Vec::new),
+            dependencies: Vec::new(),
+        }
+    }
+
+    /// A plan with an installed and a placeholder core canister, a backend canister and a frontend canister.
+    ///
+    /// None of the wasm files exist, so nothing can be hashed locally.
+    fn plan() -> InstallPlan {
+        InstallPlan {
+            ic_commit: "0123456789abcdef0123456789abcdef01234567".to_string(),
+            wasm_dir: PathBuf::from("no-such-wasm-dir"),
+            nns_init: NnsInitOpts {
+                test_accounts: Vec::new(),
+                sns_subnets: Vec::new(),
+                local_registry_file: None,
+                neurons: Vec::new(),
+            },
+            core_canisters: vec![
+                canister("nns-governance", 1, true),
+                canister("nns-identity", 2, false),
+            ],
+            canisters: vec![
+                canister("nns-icp-index", 3, false),
+                canister("nns-dapp", 4, false),
+            ],
+            upload_sns_wasms: false,
+            nns_url: Url::parse("http://localhost:4943").unwrap(),
+            cmc_subnet_ids: Vec::new(),
+            icp_xdr_permyriad: None,
+        }
+    }
+
+    /// The details read from the network, with the given module hash for each canister ID.
+    fn details(module_hashes: &[(u8, Option<&str>)], frontend: &[u8]) -> NnsDetails {
+        let mut details = NnsDetails {
+            ic_commit: plan().ic_commit,
+            backend_canisters: Vec::new(),
+            frontend_canisters: Vec::new(),
+            test_accounts: Vec::new(),
+            subnets: Subnets {
+                nns: Principal::anonymous(),
+                ii: None,
+                fiduciary: None,
+                sns: Principal::anonymous(),
+                app: Vec::new(),
+            },
+        };
+        for (id, module_hash) in module_hashes {
+            let canister = CanisterDetails {
+                name: format!("canister-{id}"),
+                canister_id: Principal::from_slice(&[*id]),
+                subnet_id: Principal::anonymous(),
+                url: format!("http://localhost:4943/?canisterId={id}"),
+                module_hash: module_hash.map(str::to_string),
+            };
+            if frontend.contains(id) {
+                details.frontend_canisters.push(canister);
+            } else {
+                details.backend_canisters.push(canister);
+            }
+        }
+        details
+    }
+
+    /// The recorded hash of each canister, by name.
+    fn hashes(state: &InstallState) -> Vec<(&str, Option<&str>)> {
+        state
+            .canisters
+            .iter()
+            .map(|canister| {
+                (
+                    canister.canister_name.as_str(),
+                    canister.wasm_sha256.as_deref(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    /// Canisters that were skipped because they already existed are recorded with the module they run, and the
+    /// placeholder without a hash.
+    fn records_the_modules_on_the_network() {
+        let state = InstallState::from_details(
+            &plan(),
+            &details(
+                &[(1, Some("aa")), (2, None), (3, Some("bb")), (4, Some("cc"))],
+                &[4],
+            ),
+        );
+        assert_eq!(
+            hashes(&state),
+            [
+                ("nns-governance", Some("aa")),
+                ("nns-identity", None),
+                ("nns-icp-index", Some("bb")),
+                ("nns-dapp", Some("cc")),
+            ]
+        );
+        assert_eq!(
+            state.frontend_urls.get("canister-4").map(String::as_str),
+            Some("http://localhost:4943/?canisterId=4")
+        );
+    }
+
+    #[test]
+    /// Installing again after `dfx nns upgrade` keeps the hash of the upgraded module.
+    fn keeps_upgraded_modules() {
+        let plan = plan();
+        let mut state = InstallState::from_details(&plan, &details(&[(1, Some("aa"))], &[]));
+        state.record_wasm("nns-governance", &[0xdd]);
+        assert_eq!(hashes(&state)[0], ("nns-governance", Some("dd")));
+
+        let state = InstallState::from_details(&plan, &details(&[(1, Some("dd"))], &[]));
+        assert_eq!(hashes(&state)[0], ("nns-governance", Some("dd")));
+    }
+}
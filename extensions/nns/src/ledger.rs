@@ -0,0 +1,579 @@
+//! Talks to the ICP ledger and index canisters.
+#![warn(missing_docs)]
+#![warn(clippy::missing_docs_in_private_items)]
+
+use crate::nns_types::account_identifier::{AccountIdentifier, Subaccount};
+use crate::nns_types::icpts::ICPTs;
+use dfx_extensions_utils::dependencies::download_wasms::nns::{ICP_INDEX, NNS_LEDGER};
+
+use anyhow::{anyhow, bail, Context};
+use candid::{CandidType, Decode, Encode, Nat};
+use fn_error_context::context;
+use ic_agent::export::Principal;
+use ic_agent::Agent;
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The alphabet of the base32 encoding used in ICRC-1 account checksums.
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// An ICP account given on the command line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Account {
+    /// A hex account identifier, with its checksum.
+    Identifier(AccountIdentifier),
+    /// An ICRC-1 account: a principal, in which case the subaccount is `None`, or a textual ICRC-1 account.
+    Icrc1 {
+        /// The owner of the account.
+        owner: Principal,
+        /// The subaccount, or `None` for the main account.
+        subaccount: Option<Subaccount>,
+    },
+}
+
+impl Account {
+    /// The account identifier that the ledger's legacy methods use for this account.
+    pub fn account_identifier(&self) -> AccountIdentifier {
+        match self {
+            Account::Identifier(account_identifier) => *account_identifier,
+            Account::Icrc1 { owner, subaccount } => AccountIdentifier::new(*owner, *subaccount),
+        }
+    }
+}
+
+impl FromStr for Account {
+    type Err = anyhow::Error;
+
+    /// Parses a hex account identifier, a principal or a textual ICRC-1 account such as `<principal>-<checksum>.<subaccount>`.
+    fn from_str(account: &str) -> Result<Self, Self::Err> {
+        let account = account.trim();
+        if let Some((owner_and_checksum, subaccount)) = account.rsplit_once('.') {
+            return parse_icrc1_account(owner_and_checksum, subaccount)
+                .with_context(|| format!("Invalid ICRC-1 account '{account}'."));
+        }
+        if account.len() == 64 && account.chars().all(|c| c.is_ascii_hexdigit()) {
+            return AccountIdentifier::from_hex(account)
+                .map(Account::Identifier)
+                .map_err(|err| anyhow!("Invalid account identifier '{account}': {err}"));
+        }
+        Principal::from_text(account)
+            .map(|owner| Account::Icrc1 {
+                owner,
+                subaccount: None,
+            })
+            .map_err(|_| {
+                anyhow!(
+                    "'{account}' is not an account identifier, a principal or an ICRC-1 account."
+                )
+            })
+    }
+}
+
+impl fmt::Display for Account {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Account::Identifier(account_identifier) => write!(f, "{account_identifier}"),
+            Account::Icrc1 {
+                owner,
+                subaccount: None,
+            } => write!(f, "{owner}"),
+            Account::Icrc1 {
+                owner,
+                subaccount: Some(subaccount),
+            } => {
+                let hex = hex::encode(subaccount.0);
+                write!(
+                    f,
+                    "{owner}-{}.{}",
+                    icrc1_checksum(owner, subaccount),
+                    hex.trim_start_matches('0')
+                )
+            }
+        }
+    }
+}
+
+/// Parses the parts of a textual ICRC-1 account with a subaccount, `<owner>-<checksum>` and `<subaccount>`.
+fn parse_icrc1_account(owner_and_checksum: &str, subaccount: &str) -> anyhow::Result<Account> {
+    let (owner, checksum) = owner_and_checksum
+        .rsplit_once('-')
+        .ok_or_else(|| anyhow!("The checksum is missing."))?;
+    let owner = Principal::from_text(owner).context("The owner is not a principal.")?;
+    if subaccount.is_empty() || subaccount.starts_with('0') || subaccount.len() > 64 {
+        bail!("The subaccount must be given in hex without leading zeros.");
+    }
+    let subaccount: Subaccount = format!("{subaccount:0>64}").parse()?;
+    if icrc1_checksum(&owner, &subaccount) != checksum {
+        bail!("The checksum is wrong.");
+    }
+    Ok(Account::Icrc1 {
+        owner,
+        subaccount: Some(subaccount),
+    })
+}
+
+/// The checksum of a textual ICRC-1 account: the base32 encoded CRC-32 of the owner and the subaccount.
+fn icrc1_checksum(owner: &Principal, subaccount: &Subaccount) -> String {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(owner.as_slice());
+    hasher.update(&subaccount.0);
+    let mut checksum = String::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for byte in hasher.finalize().to_be_bytes() {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            checksum.push(BASE32_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        checksum.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    checksum
+}
+
+/// Argument of the ledger's `account_balance` method.
+#[derive(CandidType)]
+struct AccountBalanceArgs {
+    /// The account identifier, with its checksum.
+    account: Vec<u8>,
+}
+
+/// Argument of the ledger's `transfer` method.
+#[derive(CandidType)]
+struct TransferArgs {
+    /// A number the receiver can use to identify the transfer.
+    memo: u64,
+    /// The amount to transfer.
+    amount: ICPTs,
+    /// The fee the sender pays.
+    fee: ICPTs,
+    /// The subaccount of the caller to transfer from.  Defaults to the main account.
+    from_subaccount: Option<Vec<u8>>,
+    /// The receiving account identifier, with its checksum.
+    to: Vec<u8>,
+    /// When the transfer was created.  The ledger rejects a second transfer with the same arguments.
+    created_at_time: Option<TimeStamp>,
+}
+
+/// A point in time.
+#[derive(CandidType, Deserialize)]
+pub struct TimeStamp {
+    /// Nanoseconds since the UNIX epoch.
+    pub timestamp_nanos: u64,
+}
+
+/// Why the ledger rejected a transfer.
+#[derive(Debug, CandidType, Deserialize)]
+enum TransferError {
+    /// The fee is not the ledger's fee.
+    BadFee {
+        /// The ledger's fee.
+        expected_fee: ICPTs,
+    },
+    /// The sender's balance is too low.
+    InsufficientFunds {
+        /// The sender's balance.
+        balance: ICPTs,
+    },
+    /// The transfer is too old.
+    TxTooOld {
+        /// How old a transfer may be.
+        allowed_window_nanos: u64,
+    },
+    /// The transfer was created in the future.
+    TxCreatedInFuture,
+    /// The same transfer was made before.
+    TxDuplicate {
+        /// The block of the earlier transfer.
+        duplicate_of: u64,
+    },
+}
+
+/// An account as ICRC methods take it.
+#[derive(CandidType)]
+struct Icrc1Account {
+    /// The owner of the account.
+    owner: Principal,
+    /// The subaccount, or `None` for the main account.
+    subaccount: Option<Vec<u8>>,
+}
+
+/// Argument of the ledger's `icrc2_approve` method.
+#[derive(CandidType)]
+struct ApproveArgs {
+    /// The subaccount of the caller that the allowance is for.  Defaults to the main account.
+    from_subaccount: Option<Vec<u8>>,
+    /// The account that may spend the allowance.
+    spender: Icrc1Account,
+    /// The allowance, in e8s.
+    amount: Nat,
+    /// The allowance that the spender must have now, if any.
+    expected_allowance: Option<Nat>,
+    /// When the allowance expires, in nanoseconds since the UNIX epoch.
+    expires_at: Option<u64>,
+    /// The fee the caller pays.  Defaults to the ledger's fee.
+    fee: Option<Nat>,
+    /// A memo.
+    memo: Option<Vec<u8>>,
+    /// When the approval was created, in nanoseconds since the UNIX epoch.
+    created_at_time: Option<u64>,
+}
+
+/// Why the ledger rejected an approval.
+#[derive(Debug, CandidType, Deserialize)]
+enum ApproveError {
+    /// The fee is not the ledger's fee.
+    BadFee {
+        /// The ledger's fee.
+        expected_fee: Nat,
+    },
+    /// The caller cannot pay the fee.
+    InsufficientFunds {
+        /// The caller's balance.
+        balance: Nat,
+    },
+    /// The allowance is not the expected allowance.
+    AllowanceChanged {
+        /// The current allowance.
+        current_allowance: Nat,
+    },
+    /// The expiry is in the past.
+    Expired {
+        /// The time of the ledger.
+        ledger_time: u64,
+    },
+    /// The approval is too old.
+    TooOld,
+    /// The approval was created in the future.
+    CreatedInFuture {
+        /// The time of the ledger.
+        ledger_time: u64,
+    },
+    /// The same approval was made before.
+    Duplicate {
+        /// The block of the earlier approval.
+        duplicate_of: Nat,
+    },
+    /// The ledger cannot process approvals now.
+    TemporarilyUnavailable,
+    /// Another error.
+    GenericError {
+        /// The error code.
+        error_code: Nat,
+        /// A description of the error.
+        message: String,
+    },
+}
+
+/// Argument of the index canister's `get_account_identifier_transactions` method.
+#[derive(CandidType)]
+struct GetAccountIdentifierTransactionsArgs {
+    /// The account identifier, in hex.
+    account_identifier: String,
+    /// The newest transaction to return.  Defaults to the newest transaction of the account.
+    start: Option<u64>,
+    /// The maximum number of transactions to return.
+    max_results: u64,
+}
+
+/// What a transaction did.  Accounts are hex account identifiers.
+#[derive(CandidType, Deserialize)]
+pub enum Operation {
+    /// An allowance was approved.
+    Approve {
+        /// The account that approved the allowance.
+        from: String,
+        /// The account that may spend the allowance.
+        spender: String,
+        /// The allowance.
+        allowance: ICPTs,
+        /// The fee.
+        fee: ICPTs,
+    },
+    /// ICP was burned.
+    Burn {
+        /// The account the ICP was burned from.
+        from: String,
+        /// The amount.
+        amount: ICPTs,
+    },
+    /// ICP was minted.
+    Mint {
+        /// The account the ICP was minted to.
+        to: String,
+        /// The amount.
+        amount: ICPTs,
+    },
+    /// ICP was transferred.
+    Transfer {
+        /// The sending account.
+        from: String,
+        /// The receiving account.
+        to: String,
+        /// The amount.
+        amount: ICPTs,
+        /// The fee.
+        fee: ICPTs,
+    },
+}
+
+/// The part of a ledger transaction that `dfx nns ledger history` shows.
+#[derive(CandidType, Deserialize)]
+pub struct Transaction {
+    /// The memo.
+    pub memo: u64,
+    /// What the transaction did.
+    pub operation: Operation,
+    /// When the ledger recorded the transaction.
+    pub timestamp: Option<TimeStamp>,
+}
+
+/// A transaction and its ID, which is its ledger block.
+#[derive(CandidType, Deserialize)]
+pub struct TransactionWithId {
+    /// The ledger block of the transaction.
+    pub id: u64,
+    /// The transaction.
+    pub transaction: Transaction,
+}
+
+/// The result of the index canister's `get_account_identifier_transactions` method.
+#[derive(CandidType, Deserialize)]
+struct GetAccountIdentifierTransactionsResponse {
+    /// The transactions, newest first.
+    transactions: Vec<TransactionWithId>,
+}
+
+/// An error of the index canister's `get_account_identifier_transactions` method.
+#[derive(CandidType, Deserialize)]
+struct GetAccountIdentifierTransactionsError {
+    /// A description of the error.
+    message: String,
+}
+
+/// The balance of an account.
+#[context("Failed to get the balance of {}.", account)]
+pub async fn account_balance(agent: &Agent, account: AccountIdentifier) -> anyhow::Result<ICPTs> {
+    let ledger_id = Principal::from_text(NNS_LEDGER.canister_id)?;
+    let arg = AccountBalanceArgs {
+        account: account.to_vec(),
+    };
+    let response = agent
+        .query(&ledger_id, "account_balance")
+        .with_arg(Encode!(&arg)?)
+        .call()
+        .await?;
+    Ok(Decode!(&response, ICPTs)?)
+}
+
+/// Transfers ICP from an account of the caller.
+///
+/// Returns the ledger block of the transfer.
+///
+/// # Errors
+/// - Returns an error if the call fails or the ledger rejects the transfer.
+#[context("Failed to transfer {} to {}.", amount, to)]
+pub async fn transfer(
+    agent: &Agent,
+    to: AccountIdentifier,
+    amount: ICPTs,
+    fee: ICPTs,
+    memo: u64,
+    from_subaccount: Option<Subaccount>,
+) -> anyhow::Result<u64> {
+    let ledger_id = Principal::from_text(NNS_LEDGER.canister_id)?;
+    let arg = TransferArgs {
+        memo,
+        amount,
+        fee,
+        from_subaccount: from_subaccount.map(Subaccount::to_vec),
+        to: to.to_vec(),
+        created_at_time: Some(TimeStamp {
+            timestamp_nanos: now_nanos()?,
+        }),
+    };
+    let response = agent
+        .update(&ledger_id, "transfer")
+        .with_arg(Encode!(&arg)?)
+        .call_and_wait()
+        .await?;
+    match Decode!(&response, Result<u64, TransferError>)? {
+        Ok(block) => Ok(block),
+        Err(TransferError::InsufficientFunds { balance }) => {
+            bail!("The balance of the sending account is only {balance}.")
+        }
+        Err(TransferError::BadFee { expected_fee }) => {
+            bail!("The ledger expects a fee of {expected_fee}.")
+        }
+        Err(error) => bail!("The ledger rejected the transfer: {error:?}"),
+    }
+}
+
+/// Lets `spender` transfer up to `amount` from an account of the caller.
+///
+/// Returns the ledger block of the approval.
+///
+/// # Errors
+/// - Returns an error if the call fails or the ledger rejects the approval.
+#[context("Failed to approve an allowance of {} for {}.", amount, spender)]
+pub async fn approve(
+    agent: &Agent,
+    spender: &Account,
+    amount: ICPTs,
+    expires_at: Option<u64>,
+    from_subaccount: Option<Subaccount>,
+) -> anyhow::Result<Nat> {
+    let Account::Icrc1 { owner, subaccount } = spender else {
+        bail!("The spender must be a principal or an ICRC-1 account, not an account identifier.");
+    };
+    let ledger_id = Principal::from_text(NNS_LEDGER.canister_id)?;
+    let arg = ApproveArgs {
+        from_subaccount: from_subaccount.map(Subaccount::to_vec),
+        spender: Icrc1Account {
+            owner: *owner,
+            subaccount: subaccount.map(Subaccount::to_vec),
+        },
+        amount: Nat::from(amount.get_e8s()),
+        expected_allowance: None,
+        expires_at,
+        fee: None,
+        memo: None,
+        created_at_time: Some(now_nanos()?),
+    };
+    let response = agent
+        .update(&ledger_id, "icrc2_approve")
+        .with_arg(Encode!(&arg)?)
+        .call_and_wait()
+        .await?;
+    match Decode!(&response, Result<Nat, ApproveError>)? {
+        Ok(block) => Ok(block),
+        Err(error) => bail!("The ledger rejected the approval: {error:?}"),
+    }
+}
+
+/// The transactions of an account, newest first, as recorded by the ICP index canister.
+///
+/// # Errors
+/// - Returns an error if the call fails, for example because the index canister is not installed.
+#[context("Failed to get the transactions of {}.", account)]
+pub async fn account_transactions(
+    agent: &Agent,
+    account: AccountIdentifier,
+    start: Option<u64>,
+    max_results: u64,
+) -> anyhow::Result<Vec<TransactionWithId>> {
+    let index_id = Principal::from_text(ICP_INDEX.canister_id)?;
+    let arg = GetAccountIdentifierTransactionsArgs {
+        account_identifier: account.to_hex(),
+        start,
+        max_results,
+    };
+    let response = agent
+        .query(&index_id, "get_account_identifier_transactions")
+        .with_arg(Encode!(&arg)?)
+        .call()
+        .await?;
+    match Decode!(
+        &response,
+        Result<GetAccountIdentifierTransactionsResponse, GetAccountIdentifierTransactionsError>
+    )? {
+        Ok(response) => Ok(response.transactions),
+        Err(error) => bail!("The index canister returned an error: {}", error.message),
+    }
+}
+
+/// The current time in nanoseconds since the UNIX epoch.
+fn now_nanos() -> anyhow::Result<u64> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)?
+        .as_nanos()
+        .try_into()?)
+}
+
+#[cfg(test)]
+/// Tests of account parsing, with the examples of the ICRC-1 textual encoding specification.
+mod tests {
+    use super::*;
+
+    /// The owner of the accounts in the ICRC-1 examples.
+    const OWNER: &str = "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae";
+
+    /// An ICRC-1 account of `OWNER`.
+    fn icrc1(subaccount: Option<[u8; 32]>) -> Account {
+        Account::Icrc1 {
+            owner: Principal::from_text(OWNER).unwrap(),
+            subaccount: subaccount.map(Subaccount),
+        }
+    }
+
+    #[test]
+    /// A principal is the account with the default subaccount.
+    fn parse_default_subaccount() {
+        assert_eq!(OWNER.parse::<Account>().unwrap(), icrc1(None));
+        assert_eq!(icrc1(None).to_string(), OWNER);
+    }
+
+    #[test]
+    /// Non-default subaccounts are given in hex without leading zeros, after a checksum.
+    fn parse_subaccount() {
+        let mut counting = [0; 32];
+        for (byte, value) in counting.iter_mut().zip(1..) {
+            *byte = value;
+        }
+        let text = format!(
+            "{OWNER}-dfxgiyy.102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f20"
+        );
+        assert_eq!(text.parse::<Account>().unwrap(), icrc1(Some(counting)));
+
+        let mut one = [0; 32];
+        one[31] = 1;
+        assert_eq!(
+            format!("{OWNER}-6cc627i.1").parse::<Account>().unwrap(),
+            icrc1(Some(one))
+        );
+    }
+
+    #[test]
+    /// A wrong checksum and a subaccount with leading zeros are rejected.
+    fn parse_errors() {
+        let error = |text: String| format!("{:#}", text.parse::<Account>().unwrap_err());
+        assert!(error(format!("{OWNER}-6cc627j.1")).ends_with("The checksum is wrong."));
+        assert!(error(format!("{OWNER}-6cc627i.01"))
+            .ends_with("The subaccount must be given in hex without leading zeros."));
+        assert!(error(format!("{OWNER}.1")).contains("The owner is not a principal."));
+        assert!(error("not an account".to_string()).contains("is not an account identifier"));
+    }
+
+    #[test]
+    /// Accounts are displayed in the form they are parsed from.
+    fn display_round_trip() {
+        let mut one = [0; 32];
+        one[31] = 1;
+        let identifier = Account::Identifier(icrc1(Some(one)).account_identifier());
+        for account in [icrc1(None), icrc1(Some(one)), identifier] {
+            assert_eq!(account.to_string().parse::<Account>().unwrap(), account);
+        }
+        assert_eq!(icrc1(Some(one)).to_string(), format!("{OWNER}-6cc627i.1"));
+    }
+
+    #[test]
+    /// A hex account identifier is checked against its checksum.
+    fn parse_account_identifier() {
+        let identifier = icrc1(None).account_identifier().to_string();
+        assert_eq!(
+            identifier.parse::<Account>().unwrap(),
+            Account::Identifier(icrc1(None).account_identifier())
+        );
+        let corrupted = format!(
+            "{}{}",
+            &identifier[..63],
+            if identifier.ends_with('0') { '1' } else { '0' }
+        );
+        assert!(corrupted.parse::<Account>().is_err());
+    }
+}
@@ -4,12 +4,25 @@
 use std::path::PathBuf;
 
 use clap::Parser;
+use dfx_extensions_utils::LoggingOpts;
 use tokio::runtime::Runtime;
 
+mod canister_state;
 mod commands;
+mod connection;
 mod errors;
+mod governance;
+mod install_config;
 mod install_nns;
+mod install_plan;
+mod install_state;
+mod ledger;
+mod neurons;
+mod nns_init;
+mod nns_status;
 mod nns_types;
+mod test_tokens;
+mod upgrade_nns;
 
 /// Options for `dfx nns` and its subcommands.
 #[derive(Parser)]
@@ -23,6 +36,10 @@ pub struct NnsOpts {
     /// Path to cache of DFX which executed this extension.
     #[arg(long, env = "DFX_CACHE_PATH", global = true)]
     dfx_cache_path: Option<PathBuf>,
+
+    /// How much progress to report, and in which format.
+    #[command(flatten)]
+    logging: LoggingOpts,
 }
 
 /// Command line options for subcommands of `dfx nns`.
@@ -32,6 +49,16 @@ enum SubCommand {
     Import(commands::import::ImportOpts),
     /// Install an NNS on the local dfx server.
     Install(commands::install::InstallOpts),
+    /// Show and move ICP on the NNS ledger.
+    Ledger(commands::ledger::LedgerOpts),
+    /// Stake and manage NNS neurons.
+    Neuron(commands::neuron::NeuronOpts),
+    /// Submit an NNS proposal.
+    Propose(commands::propose::ProposeOpts),
+    /// Show which NNS canisters are installed on the local dfx server.
+    Status(commands::status::StatusOpts),
+    /// Upgrade an NNS canister on the local dfx server.
+    Upgrade(commands::upgrade::UpgradeOpts),
 }
 
 /// Executes `dfx nns` and its subcommands.
@@ -42,12 +69,18 @@ pub fn main() -> anyhow::Result<()> {
             "Missing path to dfx cache. Pass it as CLI argument: `--dfx-cache-path=PATH`",
         )
     })?;
+    let logger = &opts.logging.logger();
 
     let runtime = Runtime::new().expect("Unable to create a runtime");
     runtime.block_on(async {
         match opts.subcmd {
-            SubCommand::Import(v) => commands::import::exec(v, dfx_cache_path).await,
-            SubCommand::Install(v) => commands::install::exec(v, dfx_cache_path).await,
+            SubCommand::Import(v) => commands::import::exec(v, dfx_cache_path, logger).await,
+            SubCommand::Install(v) => commands::install::exec(v, dfx_cache_path, logger).await,
+            SubCommand::Ledger(v) => commands::ledger::exec(v, dfx_cache_path).await,
+            SubCommand::Neuron(v) => commands::neuron::exec(v, dfx_cache_path).await,
+            SubCommand::Propose(v) => commands::propose::exec(v, dfx_cache_path).await,
+            SubCommand::Status(v) => commands::status::exec(v, dfx_cache_path).await,
+            SubCommand::Upgrade(v) => commands::upgrade::exec(v, dfx_cache_path, logger).await,
         }
     })
 }
@@ -0,0 +1,487 @@
+//! NNS neurons that `dfx nns install` creates when it initializes the governance canister.
+//!
+//! Neurons are declared with `--neuron` or in `nns.json`.  Their owners may be principals or dfx identities.
+#![warn(missing_docs)]
+#![warn(clippy::missing_docs_in_private_items)]
+
+use crate::nns_types::account_identifier::Subaccount;
+use crate::nns_types::icpts::{ICPTs, ICP_SUBDIVIDABLE_BY};
+
+use anyhow::{anyhow, bail, Context};
+use dfx_core::interface::builder::IdentityPicker;
+use dfx_core::DfxInterface;
+use fn_error_context::context;
+use ic_agent::export::Principal;
+use ic_base_types::PrincipalId;
+use ic_nervous_system_common_test_keys::{
+    TEST_NEURON_1_ID, TEST_NEURON_1_OWNER_PRINCIPAL, TEST_NEURON_2_ID,
+    TEST_NEURON_2_OWNER_PRINCIPAL, TEST_NEURON_3_ID, TEST_NEURON_3_OWNER_PRINCIPAL,
+};
+use ic_nns_common::pb::v1::NeuronId;
+use ic_nns_governance_api::neuron::{DissolveState, Followees};
+use ic_nns_governance_api::{KnownNeuronData, Neuron};
+use serde::{Deserialize, Deserializer};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// The ID of the first declared neuron.  The `n`-th declared neuron, counting from zero, gets the ID
+/// `FIRST_SEEDED_NEURON_ID + n`, so that neurons can follow each other.
+pub const FIRST_SEEDED_NEURON_ID: u64 = 1_000;
+
+/// The stake of a neuron unless the declaration says otherwise.
+const DEFAULT_STAKE: ICPTs = ICPTs::from_e8s(10 * ICP_SUBDIVIDABLE_BY);
+
+/// The dissolve delay of a neuron unless the declaration says otherwise: six months, the minimum for voting.
+const DEFAULT_DISSOLVE_DELAY_SECONDS: u64 = 15_778_800;
+
+/// The stake of the first test neuron, which outweighs any declared neuron of a reasonable size.
+const TEST_NEURON_1_STAKE: ICPTs = ICPTs::from_e8s(1_000_000 * ICP_SUBDIVIDABLE_BY);
+
+/// The dissolve delay of the test neurons: twelve months.
+const TEST_NEURON_DISSOLVE_DELAY_SECONDS: u64 = 31_557_600;
+
+/// Proposal topics that can be given by name, with their numbers in the governance canister.
+const TOPICS: &[(&str, i32)] = &[
+    ("all", 0),
+    ("neuron-management", 1),
+    ("exchange-rate", 2),
+    ("network-economics", 3),
+    ("governance", 4),
+    ("node-admin", 5),
+    ("participant-management", 6),
+    ("subnet-management", 7),
+    ("network-canister-management", 8),
+    ("kyc", 9),
+    ("node-provider-rewards", 10),
+    ("ic-os-version-deployment", 12),
+    ("ic-os-version-election", 13),
+    ("sns-and-community-fund", 14),
+    ("api-boundary-node-management", 15),
+    ("subnet-rental", 16),
+    ("protocol-canister-management", 17),
+    ("service-nervous-system-management", 18),
+];
+
+/// Who controls a declared neuron.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NeuronOwner {
+    /// A principal.
+    Principal(Principal),
+    /// A dfx identity, by name.
+    Identity(String),
+}
+
+impl FromStr for NeuronOwner {
+    type Err = anyhow::Error;
+
+    /// Parses a principal, or else the name of a dfx identity.
+    fn from_str(owner: &str) -> Result<Self, Self::Err> {
+        let owner = owner.trim();
+        if owner.is_empty() {
+            bail!("The neuron owner is empty.");
+        }
+        Ok(match Principal::from_text(owner) {
+            Ok(principal) => NeuronOwner::Principal(principal),
+            Err(_) => NeuronOwner::Identity(owner.to_string()),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for NeuronOwner {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// A neuron that another neuron follows on a topic.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Followee {
+    /// The topic, by name or number.  `all` is the catch-all topic.
+    #[serde(deserialize_with = "deserialize_topic")]
+    pub topic: i32,
+    /// The followed neuron.
+    pub neuron_id: u64,
+}
+
+impl FromStr for Followee {
+    type Err = anyhow::Error;
+
+    /// Parses `TOPIC:NEURON_ID`.
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let (topic, neuron_id) = spec
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Expected TOPIC:NEURON_ID but got '{spec}'."))?;
+        Ok(Followee {
+            topic: parse_topic(topic)?,
+            neuron_id: neuron_id
+                .parse()
+                .with_context(|| format!("Invalid neuron ID '{neuron_id}'."))?,
+        })
+    }
+}
+
+/// A neuron to create when the governance canister is initialized.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct NeuronSpec {
+    /// The principal or dfx identity that controls the neuron.
+    pub owner: NeuronOwner,
+    /// The stake, in ICP.  Defaults to 10 ICP.
+    #[serde(default, deserialize_with = "deserialize_icp")]
+    pub stake: Option<ICPTs>,
+    /// The dissolve delay in seconds.  Defaults to six months.
+    #[serde(default)]
+    pub dissolve_delay_seconds: Option<u64>,
+    /// The maturity, in ICP.
+    #[serde(default, deserialize_with = "deserialize_icp")]
+    pub maturity: Option<ICPTs>,
+    /// The name under which the neuron is a known neuron.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The neurons this neuron follows.
+    #[serde(default)]
+    pub followees: Vec<Followee>,
+}
+
+impl FromStr for NeuronSpec {
+    type Err = anyhow::Error;
+
+    /// Parses `OWNER[,stake=ICP][,dissolve_delay=SECONDS][,maturity=ICP][,name=NAME][,follow=TOPIC:NEURON_ID]...`.
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut parts = spec.split(',');
+        let mut neuron = NeuronSpec {
+            owner: parts.next().unwrap_or_default().parse()?,
+            stake: None,
+            dissolve_delay_seconds: None,
+            maturity: None,
+            name: None,
+            followees: Vec::new(),
+        };
+        for part in parts {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Expected KEY=VALUE but got '{part}'."))?;
+            match key.trim() {
+                "stake" => neuron.stake = Some(parse_icp(value)?),
+                "dissolve_delay" => {
+                    neuron.dissolve_delay_seconds = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("Invalid dissolve delay '{value}'."))?,
+                    )
+                }
+                "maturity" => neuron.maturity = Some(parse_icp(value)?),
+                "name" => neuron.name = Some(value.to_string()),
+                "follow" => neuron.followees.push(value.parse()?),
+                _ => bail!(
+                    "Unknown key '{key}'.  Expected one of: stake, dissolve_delay, maturity, name, follow."
+                ),
+            }
+        }
+        Ok(neuron)
+    }
+}
+
+/// A declared neuron whose owner has been resolved to a principal.
+#[derive(Clone, Debug)]
+pub struct SeededNeuron {
+    /// The ID of the neuron.
+    pub id: u64,
+    /// The principal that controls the neuron.
+    pub controller: Principal,
+    /// The declaration.
+    pub spec: NeuronSpec,
+}
+
+impl SeededNeuron {
+    /// The stake of the neuron.
+    pub fn stake(&self) -> ICPTs {
+        self.spec.stake.unwrap_or(DEFAULT_STAKE)
+    }
+
+    /// The dissolve delay of the neuron, in seconds.
+    pub fn dissolve_delay_seconds(&self) -> u64 {
+        self.spec
+            .dissolve_delay_seconds
+            .unwrap_or(DEFAULT_DISSOLVE_DELAY_SECONDS)
+    }
+
+    /// The neuron as the governance canister is initialized with it.
+    ///
+    /// The stake is held in the usual staking subaccount of the controller, with the neuron ID as memo.  The ledger is
+    /// initialized with that balance.
+    pub fn to_governance_neuron(&self) -> Neuron {
+        let mut followees: HashMap<i32, Followees> = HashMap::new();
+        for followee in &self.spec.followees {
+            followees
+                .entry(followee.topic)
+                .or_default()
+                .followees
+                .push(NeuronId {
+                    id: followee.neuron_id,
+                });
+        }
+        Neuron {
+            id: Some(NeuronId { id: self.id }),
+            account: neuron_staking_subaccount(&self.controller, self.id).to_vec(),
+            controller: Some(PrincipalId(self.controller)),
+            cached_neuron_stake_e8s: self.stake().get_e8s(),
+            maturity_e8s_equivalent: self.spec.maturity.map(ICPTs::get_e8s).unwrap_or_default(),
+            dissolve_state: Some(DissolveState::DissolveDelaySeconds(
+                self.dissolve_delay_seconds(),
+            )),
+            followees,
+            known_neuron_data: self.spec.name.clone().map(|name| KnownNeuronData {
+                name,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+/// The test neurons, which are controlled by the test keys of `ic-nervous-system-common-test-keys`.
+///
+/// The first test neuron holds a majority of the voting power, so that its proposals are adopted at once.
+pub fn test_neurons() -> Vec<SeededNeuron> {
+    [
+        (
+            TEST_NEURON_1_ID,
+            *TEST_NEURON_1_OWNER_PRINCIPAL,
+            TEST_NEURON_1_STAKE,
+        ),
+        (
+            TEST_NEURON_2_ID,
+            *TEST_NEURON_2_OWNER_PRINCIPAL,
+            DEFAULT_STAKE,
+        ),
+        (
+            TEST_NEURON_3_ID,
+            *TEST_NEURON_3_OWNER_PRINCIPAL,
+            DEFAULT_STAKE,
+        ),
+    ]
+    .into_iter()
+    .map(|(id, controller, stake)| SeededNeuron {
+        id,
+        controller: controller.0,
+        spec: NeuronSpec {
+            owner: NeuronOwner::Principal(controller.0),
+            stake: Some(stake),
+            dissolve_delay_seconds: Some(TEST_NEURON_DISSOLVE_DELAY_SECONDS),
+            maturity: None,
+            name: None,
+            followees: Vec::new(),
+        },
+    })
+    .collect()
+}
+
+/// The subaccount of the governance canister that holds the stake of a neuron, as computed by the governance canister.
+pub fn neuron_staking_subaccount(controller: &Principal, memo: u64) -> Subaccount {
+    let mut hasher = Sha256::new();
+    hasher.update([0x0c]);
+    hasher.update(b"neuron-stake");
+    hasher.update(controller.as_slice());
+    hasher.update(memo.to_be_bytes());
+    Subaccount(hasher.finalize().into())
+}
+
+/// Resolves the owners of the declared neurons and assigns the neuron IDs.
+///
+/// # Errors
+/// - Returns an error if a dfx identity cannot be loaded.
+#[context("Failed to resolve the owners of the declared neurons.")]
+pub async fn seed_neurons(specs: &[NeuronSpec]) -> anyhow::Result<Vec<SeededNeuron>> {
+    let mut neurons = Vec::new();
+    for (n, spec) in specs.iter().enumerate() {
+        let controller = match &spec.owner {
+            NeuronOwner::Principal(principal) => *principal,
+            NeuronOwner::Identity(name) => identity_principal(name).await?,
+        };
+        neurons.push(SeededNeuron {
+            id: FIRST_SEEDED_NEURON_ID + n as u64,
+            controller,
+            spec: spec.clone(),
+        });
+    }
+    Ok(neurons)
+}
+
+/// The principal of a dfx identity.
+#[context("Failed to load the dfx identity '{}'.", name)]
+async fn identity_principal(name: &str) -> anyhow::Result<Principal> {
+    let dfx = DfxInterface::builder()
+        .with_identity(IdentityPicker::Named(name.to_string()))
+        .build()
+        .await?;
+    dfx.agent().get_principal().map_err(|err| anyhow!(err))
+}
+
+/// Parses an amount of ICP, such as `10` or `0.5`.
+pub fn parse_icp(amount: &str) -> anyhow::Result<ICPTs> {
+    ICPTs::from_str(amount).map_err(|err| anyhow!("Invalid amount of ICP '{amount}': {err}"))
+}
+
+/// Parses a topic name from `TOPICS` or a topic number.
+pub fn parse_topic(topic: &str) -> anyhow::Result<i32> {
+    let topic = topic.trim();
+    if let Some((_, number)) = TOPICS.iter().find(|(name, _)| *name == topic) {
+        return Ok(*number);
+    }
+    topic.parse().with_context(|| {
+        format!(
+            "Unknown topic '{topic}'.  Use a topic number or one of: {}",
+            TOPICS
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })
+}
+
+/// Deserializes an optional amount of ICP given as a string, such as `"10"` or `"0.5"`.
+fn deserialize_icp<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<ICPTs>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|amount| parse_icp(&amount).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// Deserializes a topic given by name or number.
+fn deserialize_topic<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+    parse_topic(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+/// Tests of neuron declarations.
+mod tests {
+    use super::*;
+
+    #[test]
+    /// An owner alone gets the defaults.
+    fn parse_owner() {
+        assert_eq!(
+            "2vxsx-fae".parse::<NeuronSpec>().unwrap(),
+            NeuronSpec {
+                owner: NeuronOwner::Principal(Principal::anonymous()),
+                stake: None,
+                dissolve_delay_seconds: None,
+                maturity: None,
+                name: None,
+                followees: Vec::new(),
+            }
+        );
+        assert_eq!(
+            "alice".parse::<NeuronOwner>().unwrap(),
+            NeuronOwner::Identity("alice".to_string())
+        );
+    }
+
+    #[test]
+    /// Every key of the command line format is parsed.
+    fn parse_all_keys() {
+        let neuron: NeuronSpec =
+            "alice,stake=2.5,dissolve_delay=100,maturity=1,name=Alice,follow=governance:1000,follow=all:1001"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            neuron,
+            NeuronSpec {
+                owner: NeuronOwner::Identity("alice".to_string()),
+                stake: Some(ICPTs::from_e8s(250_000_000)),
+                dissolve_delay_seconds: Some(100),
+                maturity: Some(ICPTs::from_e8s(ICP_SUBDIVIDABLE_BY)),
+                name: Some("Alice".to_string()),
+                followees: vec![
+                    Followee {
+                        topic: 4,
+                        neuron_id: 1000,
+                    },
+                    Followee {
+                        topic: 0,
+                        neuron_id: 1001,
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    /// Malformed declarations are rejected with a message that names the problem.
+    fn parse_errors() {
+        let error = |spec: &str| format!("{:#}", spec.parse::<NeuronSpec>().unwrap_err());
+        assert_eq!(error(""), "The neuron owner is empty.");
+        assert_eq!(error("alice,stake"), "Expected KEY=VALUE but got 'stake'.");
+        assert!(error("alice,stake=lots").starts_with("Invalid amount of ICP 'lots'"));
+        assert!(error("alice,dissolve_delay=long").starts_with("Invalid dissolve delay 'long'."));
+        assert_eq!(
+            error("alice,follow=governance"),
+            "Expected TOPIC:NEURON_ID but got 'governance'."
+        );
+        assert!(error("alice,follow=governance:first").starts_with("Invalid neuron ID 'first'."));
+        assert!(error("alice,follow=weather:1000").starts_with("Unknown topic 'weather'."));
+        assert!(error("alice,colour=blue").starts_with("Unknown key 'colour'."));
+    }
+
+    #[test]
+    /// Topics are given by name or number.
+    fn parse_topics() {
+        assert_eq!(parse_topic("all").unwrap(), 0);
+        assert_eq!(parse_topic("sns-and-community-fund").unwrap(), 14);
+        assert_eq!(parse_topic("11").unwrap(), 11);
+        assert!(parse_topic("weather").is_err());
+    }
+
+    #[test]
+    /// The JSON format has the same fields and defaults as the command line format.
+    fn parse_json() {
+        let neurons: Vec<NeuronSpec> = serde_json::from_str(
+            r#"[{ "owner": "2vxsx-fae", "stake": "20", "followees": [{ "topic": "governance", "neuron_id": 1000 }] }]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            neurons,
+            vec!["2vxsx-fae,stake=20,follow=governance:1000"
+                .parse::<NeuronSpec>()
+                .unwrap()]
+        );
+        assert!(serde_json::from_str::<Vec<NeuronSpec>>(
+            r#"[{ "owner": "2vxsx-fae", "colour": "blue" }]"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    /// The governance neuron gets the defaults, the followees by topic and the staking subaccount of its controller.
+    fn governance_neuron() {
+        let neuron = SeededNeuron {
+            id: FIRST_SEEDED_NEURON_ID,
+            controller: Principal::anonymous(),
+            spec: "2vxsx-fae,follow=governance:1001,follow=governance:1002"
+                .parse()
+                .unwrap(),
+        }
+        .to_governance_neuron();
+        assert_eq!(neuron.id, Some(NeuronId { id: 1000 }));
+        assert_eq!(neuron.cached_neuron_stake_e8s, DEFAULT_STAKE.get_e8s());
+        assert_eq!(
+            neuron.dissolve_state,
+            Some(DissolveState::DissolveDelaySeconds(
+                DEFAULT_DISSOLVE_DELAY_SECONDS
+            ))
+        );
+        assert_eq!(
+            neuron.followees[&4].followees,
+            vec![NeuronId { id: 1001 }, NeuronId { id: 1002 }]
+        );
+        assert_eq!(
+            neuron.account,
+            neuron_staking_subaccount(&Principal::anonymous(), 1000).to_vec()
+        );
+    }
+}
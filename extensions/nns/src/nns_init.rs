@@ -0,0 +1,419 @@
+//! Creates and initializes the core NNS canisters, as `ic-nns-init` used to do.
+//!
+//! The init payloads are built here from the payload types of the canisters, and the canisters are created and
+//! installed through the agent of `dfx nns install`, so no helper binary is needed.
+#![warn(missing_docs)]
+#![warn(clippy::missing_docs_in_private_items)]
+
+use crate::canister_state::CanisterState;
+use crate::install_plan::PlannedCanister;
+use crate::neurons::{test_neurons, SeededNeuron};
+use dfx_extensions_utils::dependencies::download_wasms::nns::{
+    NNS_CYCLES_MINTING, NNS_GENESIS_TOKENS, NNS_GOVERNANCE, NNS_LEDGER, NNS_LIFELINE, NNS_REGISTRY,
+    NNS_ROOT,
+};
+use dfx_extensions_utils::{Step, NNS_SNS_WASM};
+
+use candid::{CandidType, Encode};
+use cycles_minting_canister::CyclesCanisterInitPayload;
+use ic_agent::export::Principal;
+use ic_agent::{Agent, AgentError};
+use ic_base_types::{PrincipalId, SubnetId};
+use ic_nns_constants::{GOVERNANCE_CANISTER_ID, LEDGER_CANISTER_ID};
+use ic_nns_governance_api::{Governance, NetworkEconomics};
+use ic_registry_proto_data_provider::ProtoRegistryDataProvider;
+use ic_registry_transport::pb::v1::RegistryAtomicMutateRequest;
+use ic_sns_wasm::init::SnsWasmCanisterInitPayload;
+use ic_utils::interfaces::management_canister::builders::CanisterInstallMode;
+use ic_utils::interfaces::ManagementCanister;
+use icp_ledger::{
+    AccountIdentifier, LedgerCanisterInitPayload, Subaccount, Tokens, DEFAULT_TRANSFER_FEE,
+};
+use registry_canister::init::{RegistryCanisterInitPayload, RegistryCanisterInitPayloadBuilder};
+use slog::{info, o, Logger};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// The ICP balance of each test account.
+const TEST_ACCOUNT_BALANCE_ICP: u64 = 1_000_000_000;
+
+/// The number of seconds in a day, for the voting periods of the governance canister.
+const ONE_DAY_SECONDS: u64 = 24 * 60 * 60;
+
+/// An error that stops the core NNS canisters from being set up.
+///
+/// Every step that touches a canister names that canister.
+#[derive(Debug, Error)]
+pub enum NnsInitError {
+    /// A ledger test account is not a valid account identifier.
+    #[error("Invalid ledger test account '{account}': {reason}")]
+    InvalidTestAccount {
+        /// The account as given.
+        account: String,
+        /// Why it was rejected.
+        reason: String,
+    },
+
+    /// The ledger init payload was rejected by its builder.
+    #[error("Invalid ledger init argument: {reason}")]
+    InvalidLedgerInitArg {
+        /// Why it was rejected.
+        reason: String,
+    },
+
+    /// The registry state of the local network could not be read.
+    #[error("Failed to read the initial registry from '{}'.", path.display())]
+    ReadRegistry {
+        /// The registry file written by the local network.
+        path: PathBuf,
+    },
+
+    /// An init payload could not be encoded.
+    #[error("Failed to encode the init argument of {canister_name}: {source}")]
+    EncodeInitArg {
+        /// The canister whose init argument failed to encode.
+        canister_name: String,
+        /// The encoding error.
+        source: candid::Error,
+    },
+
+    /// The wasm of a canister could not be read.
+    #[error("Failed to read the wasm of {canister_name} from '{}': {source}", path.display())]
+    ReadWasm {
+        /// The canister whose wasm is missing.
+        canister_name: String,
+        /// The path of the wasm.
+        path: PathBuf,
+        /// The I/O error.
+        source: std::io::Error,
+    },
+
+    /// A canister could not be created at its NNS canister ID.
+    #[error("Failed to create {canister_name} at {canister_id}: {source}")]
+    CreateCanister {
+        /// The canister that could not be created.
+        canister_name: String,
+        /// The ID it should have.
+        canister_id: Principal,
+        /// The error returned by the network.
+        source: AgentError,
+    },
+
+    /// A canister was created at an unexpected ID.
+    #[error("{canister_name} was created at {actual} instead of {expected}.")]
+    WrongCanisterId {
+        /// The canister that was created.
+        canister_name: String,
+        /// The ID it should have.
+        expected: Principal,
+        /// The ID it got.
+        actual: Principal,
+    },
+
+    /// A wasm could not be installed.
+    #[error("Failed to install {canister_name} at {canister_id}: {source}")]
+    InstallCode {
+        /// The canister that could not be installed.
+        canister_name: String,
+        /// The ID of the canister.
+        canister_id: Principal,
+        /// The error returned by the network.
+        source: AgentError,
+    },
+
+    /// The controllers of a canister could not be read.
+    #[error("Failed to read the controllers of {canister_name}: {source}")]
+    ReadControllers {
+        /// The canister whose controllers could not be read.
+        canister_name: String,
+        /// The error returned by the network.
+        source: AgentError,
+    },
+
+    /// Control of a canister could not be handed over.
+    #[error("Failed to make {controller} the controller of {canister_name}: {source}")]
+    SetController {
+        /// The canister whose controller could not be set.
+        canister_name: String,
+        /// The intended controller.
+        controller: Principal,
+        /// The error returned by the network.
+        source: AgentError,
+    },
+}
+
+/// Settings for the core NNS canisters.
+pub struct NnsInitOpts {
+    /// Ledger accounts that are funded with test ICP.
+    pub test_accounts: Vec<String>,
+    /// The subnets in which the nns-sns-wasm canister creates SNS canisters.
+    pub sns_subnets: Vec<Principal>,
+    /// The registry state of the local network, which the registry canister is initialized with.
+    pub local_registry_file: Option<PathBuf>,
+    /// Neurons created in addition to the test neurons.
+    pub neurons: Vec<SeededNeuron>,
+}
+
+/// The init payloads of the core canisters that take settings.
+pub struct NnsInitPayloads {
+    /// The registry records of the local network.
+    pub registry: RegistryCanisterInitPayload,
+    /// The governance state, with the test neurons and the declared neurons.
+    pub governance: Governance,
+    /// The ledger, with the stakes of the neurons and the test accounts.
+    pub ledger: LedgerCanisterInitPayload,
+    /// The settings of the cycles minting canister.
+    pub cycles_minting: Option<CyclesCanisterInitPayload>,
+    /// The settings of the nns-sns-wasm canister.
+    pub sns_wasms: SnsWasmCanisterInitPayload,
+}
+
+/// The init argument of the root and lifeline canisters, which take no settings.
+#[derive(CandidType)]
+struct EmptyInitPayload {}
+
+impl NnsInitOpts {
+    /// Builds the init payloads of the core canisters.
+    ///
+    /// The governance canister gets the test neurons, so that proposals can be made on the local network, and the
+    /// ledger is initialized with the stakes of all neurons.
+    pub fn init_payloads(&self) -> Result<NnsInitPayloads, NnsInitError> {
+        let mut registry = RegistryCanisterInitPayloadBuilder::new();
+        if let Some(path) = &self.local_registry_file {
+            for mutation in read_initial_registry_mutations(path)? {
+                registry.push_init_mutate_request(mutation);
+            }
+        }
+
+        let neurons: Vec<SeededNeuron> = test_neurons()
+            .into_iter()
+            .chain(self.neurons.iter().cloned())
+            .collect();
+        let mut initial_values = HashMap::new();
+        for neuron in &neurons {
+            let governance_neuron = neuron.to_governance_neuron();
+            let subaccount = <[u8; 32]>::try_from(governance_neuron.account.as_slice())
+                .expect("Neuron subaccounts are 32 bytes long");
+            initial_values.insert(
+                AccountIdentifier::new(GOVERNANCE_CANISTER_ID.get(), Some(Subaccount(subaccount))),
+                Tokens::from_e8s(governance_neuron.cached_neuron_stake_e8s),
+            );
+        }
+        for account in &self.test_accounts {
+            let account_identifier = AccountIdentifier::from_hex(account).map_err(|reason| {
+                NnsInitError::InvalidTestAccount {
+                    account: account.clone(),
+                    reason,
+                }
+            })?;
+            initial_values.insert(
+                account_identifier,
+                Tokens::from_tokens(TEST_ACCOUNT_BALANCE_ICP).expect("The test balance is valid"),
+            );
+        }
+        // The governance canister mints the ICP of neuron rewards.
+        let ledger = LedgerCanisterInitPayload::builder()
+            .minting_account(GOVERNANCE_CANISTER_ID.get().into())
+            .initial_values(initial_values)
+            .transfer_fee(DEFAULT_TRANSFER_FEE)
+            .token_symbol_and_name("ICP", "Internet Computer")
+            .build()
+            .map_err(|reason| NnsInitError::InvalidLedgerInitArg { reason })?;
+
+        let governance = Governance {
+            economics: Some(NetworkEconomics::with_default_values()),
+            neurons: neurons
+                .iter()
+                .map(|neuron| (neuron.id, neuron.to_governance_neuron()))
+                .collect(),
+            wait_for_quiet_threshold_seconds: 4 * ONE_DAY_SECONDS,
+            short_voting_period_seconds: ONE_DAY_SECONDS / 2,
+            neuron_management_voting_period_seconds: Some(2 * ONE_DAY_SECONDS),
+            ..Default::default()
+        };
+
+        let cycles_minting = CyclesCanisterInitPayload {
+            ledger_canister_id: Some(LEDGER_CANISTER_ID),
+            governance_canister_id: Some(GOVERNANCE_CANISTER_ID),
+            minting_account_id: Some(GOVERNANCE_CANISTER_ID.get().into()),
+            last_purged_notification: Some(1),
+            ..Default::default()
+        };
+
+        let sns_wasms = SnsWasmCanisterInitPayload {
+            sns_subnet_ids: self
+                .sns_subnets
+                .iter()
+                .map(|subnet| SubnetId::from(PrincipalId(*subnet)))
+                .collect(),
+            access_controls_enabled: true,
+            allowed_principals: Vec::new(),
+        };
+
+        Ok(NnsInitPayloads {
+            registry: registry.build(),
+            governance,
+            ledger,
+            cycles_minting: Some(cycles_minting),
+            sns_wasms,
+        })
+    }
+}
+
+/// Reads the mutations that recreate the registry of the local network.
+fn read_initial_registry_mutations(
+    path: &Path,
+) -> Result<Vec<RegistryAtomicMutateRequest>, NnsInitError> {
+    if !path.exists() {
+        return Err(NnsInitError::ReadRegistry {
+            path: path.to_path_buf(),
+        });
+    }
+    Ok(ProtoRegistryDataProvider::load_from_file(path)
+        .export_versions_as_atomic_mutation_requests())
+}
+
+/// The init argument of a core canister.
+///
+/// Returns `None` for the placeholder canisters, which are created empty so that the NNS canister IDs are allocated
+/// as on mainnet.
+pub fn core_init_arg(
+    canister_name: &str,
+    canister_id: &str,
+    payloads: &NnsInitPayloads,
+) -> Result<Option<Vec<u8>>, NnsInitError> {
+    let encode_error = |source| NnsInitError::EncodeInitArg {
+        canister_name: canister_name.to_string(),
+        source,
+    };
+    let arg = if canister_id == NNS_REGISTRY.canister_id {
+        Encode!(&payloads.registry).map_err(encode_error)?
+    } else if canister_id == NNS_GOVERNANCE.canister_id {
+        Encode!(&payloads.governance).map_err(encode_error)?
+    } else if canister_id == NNS_LEDGER.canister_id {
+        Encode!(&payloads.ledger).map_err(encode_error)?
+    } else if canister_id == NNS_ROOT.canister_id || canister_id == NNS_LIFELINE.canister_id {
+        Encode!(&EmptyInitPayload {}).map_err(encode_error)?
+    } else if canister_id == NNS_CYCLES_MINTING.canister_id {
+        Encode!(&payloads.cycles_minting).map_err(encode_error)?
+    } else if canister_id == NNS_GENESIS_TOKENS.canister_id {
+        // The genesis token canister takes a protobuf init argument.  It holds no accounts on a local network, and the
+        // empty message encodes to no bytes at all.
+        Vec::new()
+    } else if canister_id == NNS_SNS_WASM.canister_id {
+        Encode!(&payloads.sns_wasms).map_err(encode_error)?
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(arg))
+}
+
+/// Creates, installs and hands over control of the core NNS canisters.
+///
+/// All canisters are created before any is installed, as the canisters call each other during initialization.
+/// The NNS root canister then controls every core canister except itself; the lifeline canister controls the root.
+///
+/// An interrupted installation is completed canister by canister: given the current `states` of the core canisters,
+/// only missing canisters are created, only canisters without a module are installed, and controllers are only set
+/// where they differ.
+pub async fn nns_init(
+    logger: &Logger,
+    agent: &Agent,
+    wasm_dir: &Path,
+    core_canisters: &[PlannedCanister],
+    states: &[CanisterState],
+) -> Result<(), NnsInitError> {
+    let management_canister = ManagementCanister::create(agent);
+    let mut changed = false;
+    for (canister, state) in core_canisters.iter().zip(states) {
+        if state.exists() {
+            continue;
+        }
+        let (canister_id,) = management_canister
+            .create_canister()
+            .as_provisional_create_with_specified_id(canister.canister_id)
+            .call_and_wait()
+            .await
+            .map_err(|source| NnsInitError::CreateCanister {
+                canister_name: canister.canister_name.clone(),
+                canister_id: canister.canister_id,
+                source,
+            })?;
+        if canister_id != canister.canister_id {
+            return Err(NnsInitError::WrongCanisterId {
+                canister_name: canister.canister_name.clone(),
+                expected: canister.canister_id,
+                actual: canister_id,
+            });
+        }
+        changed = true;
+    }
+    for (canister, state) in core_canisters.iter().zip(states) {
+        let Some(init_arg) = &canister.init_arg else {
+            continue;
+        };
+        if matches!(state, CanisterState::Installed { .. }) {
+            continue;
+        }
+        let logger = logger.new(o!(
+            "canister" => canister.canister_name.clone(),
+            "canister_id" => canister.canister_id.to_string()
+        ));
+        let step = Step::start(&logger, format!("Installing {}", canister.canister_name));
+        let wasm_path = wasm_dir.join(&canister.wasm_name);
+        let wasm = std::fs::read(&wasm_path).map_err(|source| NnsInitError::ReadWasm {
+            canister_name: canister.canister_name.clone(),
+            path: wasm_path.clone(),
+            source,
+        })?;
+        management_canister
+            .install_code(&canister.canister_id, &wasm)
+            .with_mode(CanisterInstallMode::Install)
+            .with_raw_arg(init_arg.clone())
+            .call_and_wait()
+            .await
+            .map_err(|source| NnsInitError::InstallCode {
+                canister_name: canister.canister_name.clone(),
+                canister_id: canister.canister_id,
+                source,
+            })?;
+        step.finish();
+        changed = true;
+    }
+    let root_id = Principal::from_text(NNS_ROOT.canister_id).expect("NNS_ROOT has a valid ID");
+    let lifeline_id =
+        Principal::from_text(NNS_LIFELINE.canister_id).expect("NNS_LIFELINE has a valid ID");
+    for canister in core_canisters {
+        let controller = if canister.canister_id == root_id {
+            lifeline_id
+        } else {
+            root_id
+        };
+        let controllers = agent
+            .read_state_canister_controllers(canister.canister_id)
+            .await
+            .map_err(|source| NnsInitError::ReadControllers {
+                canister_name: canister.canister_name.clone(),
+                source,
+            })?;
+        if controllers == [controller] {
+            continue;
+        }
+        management_canister
+            .update_settings(&canister.canister_id)
+            .with_controller(controller)
+            .call_and_wait()
+            .await
+            .map_err(|source| NnsInitError::SetController {
+                canister_name: canister.canister_name.clone(),
+                controller,
+                source,
+            })?;
+        changed = true;
+    }
+    if !changed {
+        info!(logger, "The core backend canisters are already installed.");
+    }
+    Ok(())
+}
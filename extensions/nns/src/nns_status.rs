@@ -0,0 +1,262 @@
+//! Implements the `dfx nns status` command, which reports which NNS canisters are installed on a network.
+#![warn(missing_docs)]
+#![warn(clippy::missing_docs_in_private_items)]
+
+use crate::canister_state::{canister_state, file_sha256, is_not_found, CanisterState};
+use crate::install_state::{InstallState, InstalledCanister};
+
+use anyhow::Context;
+use candid::{CandidType, Decode, Encode, Nat};
+use dfx_extensions_utils::dependencies::download_wasms::nns::NNS_ROOT;
+use dfx_extensions_utils::{
+    nns_wasm_dir, IcNnsInitCanister, StandardCanister, NNS_CORE, NNS_CORE_MANUAL, NNS_FRONTEND,
+};
+use fn_error_context::context;
+use futures_util::future::try_join_all;
+use ic_agent::export::Principal;
+use ic_agent::Agent;
+use ic_utils::interfaces::ManagementCanister;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// How a canister on the network compares with the wasm that `dfx nns install` would install.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WasmStatus {
+    /// There is no canister with this ID.
+    Missing,
+    /// The canister exists but has no wasm module.
+    Empty,
+    /// The canister runs the wasm recorded by `dfx nns install`, or else the cached wasm.
+    Matching,
+    /// The canister runs a different wasm.
+    Drifted,
+    /// The canister runs a wasm but there is no cached wasm to compare it with.
+    Unverified,
+}
+
+impl std::fmt::Display for WasmStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self {
+            WasmStatus::Missing => "missing",
+            WasmStatus::Empty => "empty",
+            WasmStatus::Matching => "matching",
+            WasmStatus::Drifted => "drifted",
+            WasmStatus::Unverified => "unverified",
+        };
+        f.pad(status)
+    }
+}
+
+/// What is known about one NNS canister on the network.
+#[derive(Debug, Serialize)]
+pub struct CanisterStatus {
+    /// The name of the canister, as used by `dfx nns install`.
+    pub canister_name: String,
+    /// The ID of the canister.
+    pub canister_id: Principal,
+    /// How the installed wasm compares with the expected wasm.
+    pub status: WasmStatus,
+    /// The hex encoded SHA-256 hash of the installed wasm module, if any.
+    pub module_hash: Option<String>,
+    /// The controllers of the canister.
+    pub controllers: Vec<Principal>,
+    /// The cycles balance of the canister, if it could be read.
+    pub cycles: Option<u128>,
+}
+
+/// A canister that `dfx nns install` installs, with the hashes of the wasms that it may be running.
+struct ExpectedCanister {
+    /// The name of the canister.
+    canister_name: String,
+    /// The ID of the canister.
+    canister_id: Principal,
+    /// SHA-256 hashes of the wasms the canister may run.  The test wasm is installed for some canisters, so either is
+    /// accepted.  Empty if there is nothing to compare with.
+    wasm_hashes: Vec<Vec<u8>>,
+}
+
+impl ExpectedCanister {
+    /// Describes a canister from the IC repository, by the cached wasms.
+    fn from_ic_nns_init_canister(
+        canister: &IcNnsInitCanister,
+        wasm_dir: &Path,
+    ) -> anyhow::Result<Self> {
+        Self::from_cached_wasms(
+            canister.canister_name,
+            canister.canister_id,
+            [Some(canister.wasm_name), canister.test_wasm_name]
+                .into_iter()
+                .flatten()
+                .map(|wasm_name| wasm_dir.join(wasm_name)),
+        )
+    }
+
+    /// Describes a canister whose wasm is downloaded from outside the IC repository, by the cached wasm.
+    fn from_standard_canister(
+        canister: &StandardCanister,
+        wasm_dir: &Path,
+    ) -> anyhow::Result<Self> {
+        Self::from_cached_wasms(
+            canister.canister_name,
+            canister.canister_id,
+            [wasm_dir.join(canister.wasm_name)],
+        )
+    }
+
+    /// Describes a canister by the cached wasms that exist.
+    fn from_cached_wasms(
+        canister_name: &str,
+        canister_id: &str,
+        wasm_paths: impl IntoIterator<Item = PathBuf>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            canister_name: canister_name.to_string(),
+            canister_id: Principal::from_text(canister_id)?,
+            wasm_hashes: wasm_paths
+                .into_iter()
+                .filter(|path| path.exists())
+                .map(|path| file_sha256(&path))
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+
+    /// Describes a canister as recorded by `dfx nns install`.
+    ///
+    /// Placeholder canisters are recorded without a wasm hash, so they are reported as empty rather than drifted.
+    fn from_installed_canister(canister: &InstalledCanister) -> anyhow::Result<Self> {
+        Ok(Self {
+            canister_name: canister.canister_name.clone(),
+            canister_id: canister.canister_id,
+            wasm_hashes: canister
+                .wasm_sha256
+                .iter()
+                .map(|wasm_sha256| {
+                    hex::decode(wasm_sha256).with_context(|| {
+                        format!("Malformed wasm hash of {}", canister.canister_name)
+                    })
+                })
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+}
+
+/// Gets the status of all canisters installed by `dfx nns install`.
+///
+/// If `dfx nns install` recorded what it installed, the recorded canisters and wasms are expected.  Otherwise the
+/// default canisters are expected to run the cached wasms.
+#[context("Failed to get the status of the NNS canisters.")]
+pub async fn nns_status(
+    agent: &Agent,
+    dfx_cache_path: &Path,
+    install_state: Option<&InstallState>,
+) -> anyhow::Result<Vec<CanisterStatus>> {
+    let expected: Vec<ExpectedCanister> = match install_state {
+        Some(install_state) => install_state
+            .canisters
+            .iter()
+            .map(ExpectedCanister::from_installed_canister)
+            .collect::<anyhow::Result<_>>()?,
+        None => {
+            let wasm_dir = nns_wasm_dir(dfx_cache_path);
+            NNS_CORE
+                .iter()
+                .chain(NNS_CORE_MANUAL.iter())
+                .map(|canister| ExpectedCanister::from_ic_nns_init_canister(canister, &wasm_dir))
+                .chain(
+                    NNS_FRONTEND.iter().map(|canister| {
+                        ExpectedCanister::from_standard_canister(canister, &wasm_dir)
+                    }),
+                )
+                .collect::<anyhow::Result<_>>()?
+        }
+    };
+    try_join_all(
+        expected
+            .iter()
+            .map(|canister| canister_status(agent, canister)),
+    )
+    .await
+}
+
+/// Gets the status of one canister.
+#[context("Failed to get the status of {}.", canister.canister_name)]
+async fn canister_status(
+    agent: &Agent,
+    canister: &ExpectedCanister,
+) -> anyhow::Result<CanisterStatus> {
+    let canister_id = canister.canister_id;
+    let state = canister_state(agent, canister_id).await?;
+    let (status, module_hash) = match &state {
+        CanisterState::Missing => (WasmStatus::Missing, None),
+        CanisterState::Empty => (WasmStatus::Empty, None),
+        CanisterState::Installed { module_hash } => {
+            let status = if canister.wasm_hashes.is_empty() {
+                WasmStatus::Unverified
+            } else if canister.wasm_hashes.iter().any(|hash| state.runs(hash)) {
+                WasmStatus::Matching
+            } else {
+                WasmStatus::Drifted
+            };
+            (status, Some(hex::encode(module_hash)))
+        }
+    };
+    let (controllers, cycles) = if state.exists() {
+        let controllers = match agent.read_state_canister_controllers(canister_id).await {
+            Ok(controllers) => controllers,
+            Err(err) if is_not_found(&err) => Vec::new(),
+            Err(err) => return Err(err).context("Failed to read the controllers."),
+        };
+        (controllers, cycles_balance(agent, canister_id).await)
+    } else {
+        (Vec::new(), None)
+    };
+    Ok(CanisterStatus {
+        canister_name: canister.canister_name.clone(),
+        canister_id,
+        status,
+        module_hash,
+        controllers,
+        cycles,
+    })
+}
+
+/// Argument of the `canister_status` method of the NNS root canister.
+#[derive(CandidType)]
+struct CanisterIdRecord {
+    /// The canister to get the status of.
+    canister_id: Principal,
+}
+
+/// The part of the NNS root canister's `canister_status` response that is used here.
+#[derive(Deserialize)]
+struct RootCanisterStatusResult {
+    /// The cycles balance of the canister.
+    cycles: Nat,
+}
+
+/// Reads the cycles balance of a canister.
+///
+/// Only controllers may read the balance.  `dfx nns install` creates some canisters with the anonymous identity,
+/// so the management canister is asked first.  The core canisters are controlled by the NNS root canister,
+/// which reports the status of the canisters it controls.
+async fn cycles_balance(agent: &Agent, canister_id: Principal) -> Option<u128> {
+    let cycles = match ManagementCanister::create(agent)
+        .canister_status(&canister_id)
+        .call_and_wait()
+        .await
+    {
+        Ok((status,)) => status.cycles,
+        Err(_) => {
+            let root_canister_id = Principal::from_text(NNS_ROOT.canister_id).ok()?;
+            let response = agent
+                .update(&root_canister_id, "canister_status")
+                .with_arg(Encode!(&CanisterIdRecord { canister_id }).ok()?)
+                .call_and_wait()
+                .await
+                .ok()?;
+            Decode!(&response, RootCanisterStatusResult).ok()?.cycles
+        }
+    };
+    u128::try_from(cycles.0).ok()
+}
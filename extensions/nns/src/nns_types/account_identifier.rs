@@ -13,9 +13,7 @@ use std::convert::{TryFrom, TryInto};
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
-#[allow(dead_code)]
 const SUB_ACCOUNT_ZERO: Subaccount = Subaccount([0; 32]);
-#[allow(dead_code)]
 const ACCOUNT_DOMAIN_SEPERATOR: &[u8] = b"\x0Aaccount-id";
 
 /// While this is backed by an array of length 28, it's canonical representation
@@ -31,7 +29,6 @@ pub struct AccountIdentifier {
 }
 
 impl AccountIdentifier {
-    #[allow(dead_code)]
     pub fn new(account: Principal, sub_account: Option<Subaccount>) -> AccountIdentifier {
         let mut hash = Sha224::new();
         hash.update(ACCOUNT_DOMAIN_SEPERATOR);
@@ -172,7 +169,6 @@ impl CandidType for AccountIdentifier {
 pub struct Subaccount(pub [u8; 32]);
 
 impl Subaccount {
-    #[allow(dead_code)]
     pub fn to_vec(self) -> Vec<u8> {
         self.0.to_vec()
     }
@@ -0,0 +1,345 @@
+//! ICRC-1 test tokens that `dfx nns install` deploys on request.
+//!
+//! Tokens are declared with `--test-token` or in a JSON file passed with `--test-tokens-file`.
+#![warn(missing_docs)]
+#![warn(clippy::missing_docs_in_private_items)]
+
+use anyhow::{anyhow, bail, Context};
+use candid::Encode;
+use fn_error_context::context;
+use ic_agent::export::Principal;
+use ic_icrc1_index_ng::{IndexArg, InitArg as IndexInitArg};
+use ic_icrc1_ledger::{InitArgsBuilder, LedgerArgument};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The index, in the canister ID range of the NNS subnet, of the ledger of the first test token.
+///
+/// Test token `n` (counting from zero) has its ledger at index `FIRST_TEST_TOKEN_INDEX + 2n`
+/// and its index canister at the index after that, whether or not the index canister is installed.
+const FIRST_TEST_TOKEN_INDEX: u64 = 0x1000;
+
+/// An ICRC-1 ledger to install for testing.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TestToken {
+    /// The token symbol, e.g. `ckETH`.
+    pub symbol: String,
+    /// The token name.  Defaults to the symbol.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The number of decimals.  Defaults to that of the ledger, which is 8.
+    #[serde(default)]
+    pub decimals: Option<u8>,
+    /// The transfer fee, in the smallest unit of the token.  Defaults to that of the ledger test configuration.
+    #[serde(default)]
+    pub fee: Option<u64>,
+    /// Balances minted when the ledger is created, in the smallest unit of the token.
+    #[serde(default)]
+    pub initial_balances: Vec<InitialBalance>,
+    /// Whether to install an index canister for the ledger.
+    #[serde(default = "default_index")]
+    pub index: bool,
+}
+
+/// A balance minted when a test token ledger is created.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct InitialBalance {
+    /// The principal that owns the balance, in its default subaccount.
+    pub owner: Principal,
+    /// The amount, in the smallest unit of the token.
+    pub amount: u128,
+}
+
+/// Test tokens get an index canister unless the declaration says otherwise.
+fn default_index() -> bool {
+    true
+}
+
+impl FromStr for TestToken {
+    type Err = anyhow::Error;
+
+    /// Parses `SYMBOL[,name=NAME][,decimals=N][,fee=N][,balance=PRINCIPAL:AMOUNT]...[,index=false]`.
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut parts = spec.split(',');
+        let symbol = parts.next().unwrap_or_default().trim().to_string();
+        let mut token = TestToken {
+            symbol,
+            name: None,
+            decimals: None,
+            fee: None,
+            initial_balances: Vec::new(),
+            index: default_index(),
+        };
+        for part in parts {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Expected KEY=VALUE but got '{part}'."))?;
+            match key.trim() {
+                "name" => token.name = Some(value.to_string()),
+                "decimals" => {
+                    token.decimals = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("Invalid decimals '{value}'."))?,
+                    )
+                }
+                "fee" => {
+                    token.fee = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("Invalid fee '{value}'."))?,
+                    )
+                }
+                "balance" => {
+                    let (owner, amount) = value.split_once(':').ok_or_else(|| {
+                        anyhow!("Expected balance=PRINCIPAL:AMOUNT but got 'balance={value}'.")
+                    })?;
+                    token.initial_balances.push(InitialBalance {
+                        owner: Principal::from_text(owner)
+                            .with_context(|| format!("Invalid principal '{owner}'."))?,
+                        amount: amount
+                            .parse()
+                            .with_context(|| format!("Invalid amount '{amount}'."))?,
+                    });
+                }
+                "index" => {
+                    token.index = value.parse().with_context(|| {
+                        format!("Expected index=true or index=false but got 'index={value}'.")
+                    })?
+                }
+                _ => bail!(
+                    "Unknown key '{key}'.  Expected one of: name, decimals, fee, balance, index."
+                ),
+            }
+        }
+        Ok(token)
+    }
+}
+
+impl TestToken {
+    /// The ID of the ledger of the `n`-th test token.
+    pub fn ledger_id(n: usize) -> Principal {
+        nns_range_canister_id(FIRST_TEST_TOKEN_INDEX + 2 * n as u64)
+    }
+
+    /// The ID of the index canister of the `n`-th test token.
+    pub fn index_id(n: usize) -> Principal {
+        nns_range_canister_id(FIRST_TEST_TOKEN_INDEX + 2 * n as u64 + 1)
+    }
+
+    /// The name of the ledger canister, as used with `--skip` and `--only`.
+    pub fn ledger_canister_name(&self) -> String {
+        format!("{}-ledger", self.symbol.to_lowercase())
+    }
+
+    /// The name of the index canister, as used with `--skip` and `--only`.
+    pub fn index_canister_name(&self) -> String {
+        format!("{}-index", self.symbol.to_lowercase())
+    }
+
+    /// The prefix of the nns-dapp configuration keys for this token, e.g. `CKETH` for `CKETH_LEDGER_CANISTER_ID`.
+    pub fn nns_dapp_key_prefix(&self) -> String {
+        self.symbol.to_uppercase()
+    }
+
+    /// The init argument of the ledger.
+    pub fn ledger_init_arg(&self) -> anyhow::Result<Vec<u8>> {
+        let mut builder = InitArgsBuilder::for_tests()
+            .with_token_symbol(self.symbol.clone())
+            .with_token_name(self.name.clone().unwrap_or_else(|| self.symbol.clone()));
+        if let Some(decimals) = self.decimals {
+            builder = builder.with_decimals(decimals);
+        }
+        if let Some(fee) = self.fee {
+            builder = builder.with_transfer_fee(fee);
+        }
+        for InitialBalance { owner, amount } in &self.initial_balances {
+            builder = builder.with_initial_balance(*owner, *amount);
+        }
+        Ok(Encode!(&(LedgerArgument::Init(builder.build())))?)
+    }
+
+    /// The init argument of the index canister of the `n`-th test token.
+    pub fn index_init_arg(n: usize) -> anyhow::Result<Vec<u8>> {
+        Ok(Encode!(&Some(IndexArg::Init(IndexInitArg {
+            ledger_id: Self::ledger_id(n),
+            retrieve_blocks_from_ledger_interval_seconds: None,
+        })))?)
+    }
+}
+
+/// The test token that is installed when no tokens are declared: a ckETH ledger with an index canister.
+///
+/// It is installed at the `ICRC1_LEDGER` and `ICRC1_INDEX` IDs, or at the mainnet ckETH IDs on a fiduciary subnet,
+/// rather than in the test token range.
+pub fn default_test_token() -> TestToken {
+    TestToken {
+        symbol: "ckETH".to_string(),
+        name: None,
+        decimals: None,
+        fee: None,
+        initial_balances: Vec::new(),
+        index: true,
+    }
+}
+
+/// Reads the test tokens declared in a JSON file: an array of objects with the fields of [`TestToken`].
+#[context("Failed to read test tokens from '{}'.", path.display())]
+pub fn load_test_tokens(path: &Path) -> anyhow::Result<Vec<TestToken>> {
+    let json = fs::read_to_string(path).with_context(|| format!("Unable to read {:?}", path))?;
+    serde_json::from_str(&json).with_context(|| "Expected a JSON array of test tokens")
+}
+
+/// Checks that the declared test tokens can be installed side by side.
+///
+/// # Errors
+/// - Returns an error if a symbol is empty or not alphanumeric, or if two tokens have the same symbol.
+pub fn validate_test_tokens(tokens: &[TestToken]) -> anyhow::Result<()> {
+    for (n, token) in tokens.iter().enumerate() {
+        if token.symbol.is_empty() || !token.symbol.chars().all(|c| c.is_ascii_alphanumeric()) {
+            bail!(
+                "Invalid test token symbol '{}'.  Symbols must be non-empty and alphanumeric.",
+                token.symbol
+            );
+        }
+        if tokens[..n]
+            .iter()
+            .any(|other| other.symbol.eq_ignore_ascii_case(&token.symbol))
+        {
+            bail!(
+                "The test token '{}' is declared more than once.",
+                token.symbol
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The canister ID with the given index in the canister ID range of the NNS subnet.
+fn nns_range_canister_id(index: u64) -> Principal {
+    let mut bytes = index.to_be_bytes().to_vec();
+    bytes.extend([0x01, 0x01]);
+    Principal::from_slice(&bytes)
+}
+
+#[cfg(test)]
+/// Tests of test token declarations and IDs.
+mod tests {
+    use super::*;
+
+    #[test]
+    /// A symbol alone gets the defaults.
+    fn parse_symbol() {
+        assert_eq!(
+            "TKN".parse::<TestToken>().unwrap(),
+            TestToken {
+                symbol: "TKN".to_string(),
+                name: None,
+                decimals: None,
+                fee: None,
+                initial_balances: Vec::new(),
+                index: true,
+            }
+        );
+    }
+
+    #[test]
+    /// Every key of the command line format is parsed.
+    fn parse_all_keys() {
+        let token: TestToken =
+            "TKN,name=Test Token,decimals=6,fee=100,balance=2vxsx-fae:1000000,balance=aaaaa-aa:5,index=false"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            token,
+            TestToken {
+                symbol: "TKN".to_string(),
+                name: Some("Test Token".to_string()),
+                decimals: Some(6),
+                fee: Some(100),
+                initial_balances: vec![
+                    InitialBalance {
+                        owner: Principal::anonymous(),
+                        amount: 1_000_000,
+                    },
+                    InitialBalance {
+                        owner: Principal::management_canister(),
+                        amount: 5,
+                    },
+                ],
+                index: false,
+            }
+        );
+    }
+
+    #[test]
+    /// Malformed declarations are rejected with a message that names the problem.
+    fn parse_errors() {
+        let error = |spec: &str| format!("{:#}", spec.parse::<TestToken>().unwrap_err());
+        assert_eq!(
+            error("TKN,decimals"),
+            "Expected KEY=VALUE but got 'decimals'."
+        );
+        assert!(error("TKN,decimals=many").starts_with("Invalid decimals 'many'."));
+        assert!(error("TKN,balance=2vxsx-fae").starts_with("Expected balance=PRINCIPAL:AMOUNT"));
+        assert!(error("TKN,balance=2vxsx-fae:lots").starts_with("Invalid amount 'lots'."));
+        assert!(error("TKN,index=no").starts_with("Expected index=true or index=false"));
+        assert!(error("TKN,colour=blue").starts_with("Unknown key 'colour'."));
+    }
+
+    #[test]
+    /// The JSON format has the same fields and defaults as the command line format.
+    fn parse_json() {
+        let tokens: Vec<TestToken> = serde_json::from_str(
+            r#"[{ "symbol": "TKN", "fee": 100, "initial_balances": [{ "owner": "2vxsx-fae", "amount": 7 }] }]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            tokens,
+            vec!["TKN,fee=100,balance=2vxsx-fae:7"
+                .parse::<TestToken>()
+                .unwrap()]
+        );
+        assert!(serde_json::from_str::<Vec<TestToken>>(
+            r#"[{ "symbol": "TKN", "colour": "blue" }]"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    /// Symbols must be alphanumeric and unique, ignoring case.
+    fn validate() {
+        let tokens = |specs: &[&str]| -> Vec<TestToken> {
+            specs.iter().map(|spec| spec.parse().unwrap()).collect()
+        };
+        assert!(validate_test_tokens(&tokens(&["TKN", "XYZ"])).is_ok());
+        assert!(validate_test_tokens(&tokens(&["TKN", "tkn"])).is_err());
+        assert!(validate_test_tokens(&tokens(&["T-K"])).is_err());
+        assert!(validate_test_tokens(&tokens(&[""])).is_err());
+    }
+
+    #[test]
+    /// Each token has a ledger and an index ID of its own, which do not depend on the other tokens.
+    fn deterministic_ids() {
+        assert_eq!(
+            TestToken::ledger_id(0).to_text(),
+            "3wht4-pqaaa-aaaaa-acaaa-cai"
+        );
+        assert_eq!(
+            TestToken::index_id(0).to_text(),
+            "3rgvi-ciaaa-aaaaa-acaaq-cai"
+        );
+        assert_eq!(
+            TestToken::ledger_id(1).to_text(),
+            "3yf6u-uaaaa-aaaaa-acaba-cai"
+        );
+        let token: TestToken = "ckUSDC".parse().unwrap();
+        assert_eq!(token.ledger_canister_name(), "ckusdc-ledger");
+        assert_eq!(token.index_canister_name(), "ckusdc-index");
+        assert_eq!(token.nns_dapp_key_prefix(), "CKUSDC");
+    }
+}
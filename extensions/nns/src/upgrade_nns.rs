@@ -0,0 +1,227 @@
+//! Upgrades one NNS canister on the local network the way mainnet does: with a proposal that NNS root executes.
+#![warn(missing_docs)]
+#![warn(clippy::missing_docs_in_private_items)]
+
+use crate::canister_state::{canister_state, CanisterState};
+use crate::governance::{self, Action, InstallCode, ProposalOutcome, TEST_NEURON_1_ID};
+
+use anyhow::{anyhow, bail, Context};
+use backoff::backoff::Backoff;
+use backoff::ExponentialBackoff;
+use dfx_extensions_utils::{
+    download_ic_repo_wasm, ic_repo_wasm_sha256_sums, nns_wasm_dir, IcNnsInitCanister, Step,
+    NNS_CORE,
+};
+use fn_error_context::context;
+use ic_agent::export::Principal;
+use ic_agent::Agent;
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use slog::{info, Logger};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The governance canister's number for the upgrade mode of an `InstallCode` proposal.
+const INSTALL_MODE_UPGRADE: i32 = 3;
+
+/// Where the new wasm comes from.
+pub enum WasmSource {
+    /// A local file.
+    File(PathBuf),
+    /// The wasm that the IC CI built at this commit.
+    IcCommit(String),
+}
+
+/// What `upgrade_nns_canister` did.
+pub enum UpgradeOutcome {
+    /// The canister already ran the wasm, so nothing was proposed.
+    AlreadyRunning {
+        /// The name of the canister.
+        canister_name: &'static str,
+        /// The SHA-256 hash of the wasm the canister runs.
+        wasm_hash: Vec<u8>,
+    },
+    /// The canister was upgraded.
+    Upgraded {
+        /// The name of the canister.
+        canister_name: &'static str,
+        /// The SHA-256 hash of the wasm the canister ran before.
+        old_hash: Vec<u8>,
+        /// The SHA-256 hash of the wasm the canister runs now.
+        wasm_hash: Vec<u8>,
+    },
+}
+
+impl UpgradeOutcome {
+    /// The SHA-256 hash of the wasm the canister runs.
+    pub fn wasm_hash(&self) -> &[u8] {
+        match self {
+            UpgradeOutcome::AlreadyRunning { wasm_hash, .. }
+            | UpgradeOutcome::Upgraded { wasm_hash, .. } => wasm_hash,
+        }
+    }
+}
+
+impl fmt::Display for UpgradeOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpgradeOutcome::AlreadyRunning {
+                canister_name,
+                wasm_hash,
+            } => write!(
+                f,
+                "{canister_name} already runs the wasm with hash 0x{}.",
+                hex::encode(wasm_hash)
+            ),
+            UpgradeOutcome::Upgraded {
+                canister_name,
+                old_hash,
+                wasm_hash,
+            } => write!(
+                f,
+                "Upgraded {canister_name} from 0x{} to 0x{}.",
+                hex::encode(old_hash),
+                hex::encode(wasm_hash)
+            ),
+        }
+    }
+}
+
+/// Finds a core NNS canister by name, with or without the `nns-` prefix, e.g. `nns-governance` or `governance`.
+///
+/// # Errors
+/// - Returns an error listing the canister names if there is no such canister.
+pub fn find_core_canister(name: &str) -> anyhow::Result<&'static IcNnsInitCanister> {
+    NNS_CORE
+        .iter()
+        .copied()
+        .find(|canister| {
+            canister.canister_name == name || canister.canister_name == format!("nns-{name}")
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "'{name}' is not a core NNS canister.  Expected one of: {}",
+                NNS_CORE
+                    .iter()
+                    .map(|canister| canister.canister_name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+}
+
+/// Upgrades a core NNS canister with a proposal of the test neuron and checks that it runs the new wasm afterwards.
+///
+/// Wasms from an IC commit are downloaded by `wasm_name`.  Progress is logged; the outcome is returned for the caller
+/// to report.
+///
+/// # Errors
+/// - Returns an error if the wasm cannot be obtained, if the proposal is not executed, or if the canister does not run
+///   the new wasm after the upgrade.
+#[context("Failed to upgrade {}.", canister.canister_name)]
+pub async fn upgrade_nns_canister(
+    logger: &Logger,
+    agent: &Agent,
+    provider_url: &Url,
+    dfx_cache_path: &Path,
+    canister: &IcNnsInitCanister,
+    wasm_name: &str,
+    wasm_source: &WasmSource,
+    arg: Vec<u8>,
+) -> anyhow::Result<UpgradeOutcome> {
+    let canister_id = Principal::from_text(canister.canister_id)?;
+    let wasm_module = wasm_module(logger, dfx_cache_path, wasm_name, wasm_source).await?;
+    let wasm_hash = Sha256::digest(&wasm_module).to_vec();
+
+    let old_hash = match canister_state(agent, canister_id).await? {
+        CanisterState::Installed { module_hash } => module_hash,
+        _ => bail!(
+            "{} is not installed.  Run `dfx nns install` first.",
+            canister.canister_name
+        ),
+    };
+    if old_hash == wasm_hash {
+        return Ok(UpgradeOutcome::AlreadyRunning {
+            canister_name: canister.canister_name,
+            wasm_hash,
+        });
+    }
+
+    let step = Step::start(logger, format!("Upgrading {}", canister.canister_name));
+    let neuron_agent = governance::test_neuron_agent(provider_url).await?;
+    let title = format!("Upgrade {}", canister.canister_name);
+    let summary = format!(
+        "Upgrade {} ({canister_id}) to the wasm with hash 0x{}.",
+        canister.canister_name,
+        hex::encode(&wasm_hash)
+    );
+    let action = Action::InstallCode(InstallCode {
+        canister_id: Some(canister_id),
+        wasm_module: Some(wasm_module),
+        arg: Some(arg),
+        install_mode: Some(INSTALL_MODE_UPGRADE),
+        skip_stopping_before_installing: None,
+    });
+    let proposal_id = governance::make_proposal(
+        &neuron_agent,
+        TEST_NEURON_1_ID,
+        &title,
+        &summary,
+        "",
+        action,
+    )
+    .await?;
+    info!(
+        logger,
+        "Submitted proposal {proposal_id} to {}.", title.to_lowercase();
+        "proposal_id" => proposal_id,
+        "canister" => canister.canister_name
+    );
+    match governance::wait_for_proposal(&neuron_agent, proposal_id).await? {
+        ProposalOutcome::Executed => {}
+        outcome => bail!("Proposal {proposal_id} was {outcome}"),
+    }
+
+    // The proposal is executed once NNS root has been asked to upgrade, so the upgrade itself may still be running.
+    let mut retry_policy = ExponentialBackoff::default();
+    loop {
+        if canister_state(agent, canister_id).await?.runs(&wasm_hash) {
+            break;
+        }
+        match retry_policy.next_backoff() {
+            Some(duration) => tokio::time::sleep(duration).await,
+            None => bail!(
+                "{} does not run the wasm with hash 0x{} after the upgrade.",
+                canister.canister_name,
+                hex::encode(&wasm_hash)
+            ),
+        }
+    }
+    step.finish();
+    Ok(UpgradeOutcome::Upgraded {
+        canister_name: canister.canister_name,
+        old_hash,
+        wasm_hash,
+    })
+}
+
+/// Reads the new wasm, downloading it first if it comes from an IC commit.
+///
+/// Wasms from IC commits are cached in a directory per commit.
+async fn wasm_module(
+    logger: &Logger,
+    dfx_cache_path: &Path,
+    wasm_name: &str,
+    wasm_source: &WasmSource,
+) -> anyhow::Result<Vec<u8>> {
+    let path = match wasm_source {
+        WasmSource::File(path) => path.clone(),
+        WasmSource::IcCommit(ic_commit) => {
+            let wasm_dir = nns_wasm_dir(dfx_cache_path).join(ic_commit);
+            let sha256_sums = ic_repo_wasm_sha256_sums(logger, ic_commit).await?;
+            download_ic_repo_wasm(logger, wasm_name, ic_commit, &wasm_dir, &sha256_sums).await?;
+            wasm_dir.join(wasm_name)
+        }
+    };
+    std::fs::read(&path).with_context(|| format!("Failed to read {}.", path.display()))
+}
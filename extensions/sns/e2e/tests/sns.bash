@@ -234,3 +234,28 @@ SNS_CONFIG_FILE_NAME="sns_init.yaml"
     run dfx sns neuron-id-to-candid-subaccount 9f5f9fda77a03e7177126d0be8c99e931a5381731d00da53ede363140e1be5d6 --escaped
     assert_output --partial 'blob \"\\9f\\5f\\9f\\da\\77\\a0\\3e\\71\\77\\12\\6d\\0b\\e8\\c9\\9e\\93\\1a\\53\\81\\73\\1d\\00\\da\\53\\ed\\e3\\63\\14\\0e\\1b\\e5\\d6\"'
 }
+
+@test "sns download checks the download settings" {
+    DFX_DOWNLOAD_TIMEOUT=soon run dfx sns download --wasms-dir wasms
+    assert_failure
+    assert_output --partial "DFX_DOWNLOAD_TIMEOUT must be a number of seconds, not 'soon'."
+}
+
+@test "sns download --from-dir copies the wasms into --wasms-dir" {
+    mkdir prepared
+    touch prepared/sns-root-canister.wasm prepared/sns-governance-canister.wasm prepared/sns-swap-canister.wasm
+    run dfx sns download --from-dir prepared --wasms-dir wasms
+    assert_failure
+    assert_output --partial "prepared/ic-icrc1-ledger.wasm"
+    refute_output --partial "prepared/sns-root-canister.wasm"
+
+    touch prepared/ic-icrc1-ledger.wasm prepared/ic-icrc1-archive.wasm prepared/ic-icrc1-index-ng.wasm
+    run dfx sns download --from-dir prepared --wasms-dir wasms
+    assert_success
+    assert [ -f wasms/sns-governance-canister.wasm ]
+    assert [ -f wasms/ic-icrc1-index-ng.wasm ]
+
+    DFX_IC_COMMIT=deadbeef run dfx sns download --wasm-dir prepared --wasms-dir copied
+    assert_success
+    assert [ -f copied/sns-swap-canister.wasm ]
+}
@@ -1,6 +1,8 @@
 //! Code for the command line `dfx sns import`
 use clap::Parser;
-use dfx_extensions_utils::{dependencies::dfx::NNS_SNS_REPLICA_REV, download_sns_wasms};
+use dfx_extensions_utils::{
+    copy_sns_wasms, dependencies::dfx::NNS_SNS_REPLICA_REV, download_sns_wasms, LoggingOpts,
+};
 use std::path::PathBuf;
 
 /// Downloads the SNS canister WASMs
@@ -12,11 +14,20 @@ pub struct SnsDownloadOpts {
     /// Path to store downloaded SNS canister WASMs
     #[arg(long, default_value = ".")]
     wasms_dir: PathBuf,
+    /// Copy the SNS canister WASMs from this directory into --wasms-dir instead of downloading them. The copied files are not verified against any hash
+    #[arg(long, alias = "wasm-dir")]
+    from_dir: Option<PathBuf>,
+    /// How much progress to report, and in which format.
+    #[command(flatten)]
+    logging: LoggingOpts,
 }
 
 /// Executes the command line `dfx sns import`.
 pub async fn exec(opts: SnsDownloadOpts) -> anyhow::Result<()> {
+    if let Some(from_dir) = &opts.from_dir {
+        return copy_sns_wasms(from_dir, &opts.wasms_dir);
+    }
     let ic_commit = opts.ic_commit.unwrap_or(NNS_SNS_REPLICA_REV.to_string());
-    download_sns_wasms(&ic_commit, &opts.wasms_dir).await?;
+    download_sns_wasms(&opts.logging.logger(), &ic_commit, &opts.wasms_dir).await?;
     Ok(())
 }
@@ -3,7 +3,7 @@ use std::path::Path;
 
 use dfx_core::config::model::dfinity::Config;
 use dfx_extensions_utils::dependencies::dfx::NNS_SNS_REPLICA_REV;
-use dfx_extensions_utils::{get_network_mappings, import_canister_definitions, new_logger};
+use dfx_extensions_utils::{get_network_mappings, import_canister_definitions, LoggingOpts};
 
 use clap::Parser;
 use dfx_core::config::cache::get_version_from_cache_path;
@@ -20,6 +20,10 @@ pub struct SnsImportOpts {
     ///   --network-mapping ic=mainnet
     #[arg(long, default_value = "ic=mainnet", action = clap::ArgAction::Append)]
     network_mapping: Vec<String>,
+
+    /// How much progress to report, and in which format.
+    #[command(flatten)]
+    logging: LoggingOpts,
 }
 
 /// Executes the command line `dfx sns import`.
@@ -31,7 +35,7 @@ pub async fn exec(opts: SnsImportOpts, dfx_cache_path: &Path) -> anyhow::Result<
         anyhow::bail!(crate::errors::DFXJSON_NOT_FOUND);
     }
     let mut config = config.unwrap();
-    let logger = new_logger();
+    let logger = opts.logging.logger();
 
     let network_mappings = get_network_mappings(&opts.network_mapping)?;
 